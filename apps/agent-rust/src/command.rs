@@ -1,7 +1,7 @@
 use std::process::Stdio;
 use std::time::Duration;
 
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::process::Command;
 use tokio::sync::watch;
 
@@ -39,12 +39,29 @@ pub async fn run_shell_command_limited(
     user: Option<&str>,
     workdir: Option<&str>,
     max_output_bytes: usize,
+) -> serde_json::Value {
+    run_shell_command_with_input(command, None, timeout_ms, user, workdir, max_output_bytes).await
+}
+
+/// Like `run_shell_command_limited`, with `input` written to the command's
+/// stdin. For lists too long to pass as arguments.
+pub async fn run_shell_command_with_input(
+    command: &str,
+    input: Option<Vec<u8>>,
+    timeout_ms: u64,
+    user: Option<&str>,
+    workdir: Option<&str>,
+    max_output_bytes: usize,
 ) -> serde_json::Value {
     let timeout = Duration::from_millis(timeout_ms);
 
     let mut cmd = Command::new("/bin/bash");
     cmd.args(["-l", "-c", command])
-        .stdin(Stdio::null())
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
@@ -71,6 +88,15 @@ pub async fn run_shell_command_limited(
 
     let pid = child.id().unwrap_or(0);
 
+    // Written from a task so a command that fills its stdout before reading
+    // all of stdin doesn't deadlock; a command that exits early just closes
+    // the pipe.
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&input).await;
+        });
+    }
+
     let stdout = child.stdout.take();
     let stderr = child.stderr.take();
 
@@ -11,6 +11,9 @@ pub const DEV_PORT: u16 = 3001;
 pub const DEV_APP_PORT: u16 = 5173;
 pub const LOG_DIR: &str = "/var/log/sandbox";
 pub const DEFAULT_EXEC_TIMEOUT_MS: u64 = 30_000;
+// Fetch/pull/push talk to the remote, which can take far longer than a local
+// git command on a big repo or a slow link.
+pub const GIT_NETWORK_TIMEOUT_MS: u64 = 120_000;
 
 pub const CONFIG_PATH: &str = "/etc/sandbox/config.json";
 
@@ -41,6 +44,15 @@ pub struct NetworkConfig {
 pub struct RepoConfig {
     pub clone_path: String,
     pub branch: String,
+    #[serde(default)]
+    pub checks: Vec<RepoCheckConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoCheckConfig {
+    pub name: String,
+    pub command: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +62,15 @@ pub struct DevForwarderConfig {
     pub app_port: u16,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathPolicyConfig {
+    #[serde(default)]
+    pub writable_roots: Vec<String>,
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+}
+
 pub type SandboxServices = HashMap<String, ServiceConfig>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +86,8 @@ pub struct SandboxConfig {
     pub services: SandboxServices,
     #[serde(default)]
     pub dev_forwarder: Option<DevForwarderConfig>,
+    #[serde(default)]
+    pub path_policy: Option<PathPolicyConfig>,
 }
 
 pub static SANDBOX_CONFIG: LazyLock<RwLock<Option<SandboxConfig>>> = LazyLock::new(|| {
@@ -0,0 +1,512 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::Duration;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::Response;
+use ignore::WalkBuilder;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::config::get_config;
+use crate::response::json_ok;
+use crate::routes::git::full_path;
+
+const DEFAULT_FIND_LIMIT: usize = 50;
+const MAX_FIND_LIMIT: usize = 500;
+// Stop indexing past this many files per repo; a checked-in vendor tree that
+// big would blow the agent's memory budget for little quick-open value.
+const MAX_INDEXED_FILES: usize = 500_000;
+// Editors save through temp-file + rename bursts; coalesce them into one
+// re-listing per directory.
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Paths are relative to `root`, `/`-separated; the root dir itself is `""`.
+struct RepoIndex {
+    clone_path: String,
+    root: PathBuf,
+    files: BTreeSet<String>,
+    dirs: BTreeSet<String>,
+    /// Every indexed directory is watched and nothing was cut off at
+    /// `MAX_INDEXED_FILES`. Otherwise changes can go unnoticed, and the
+    /// git status cache mustn't rely on this index's events.
+    complete: bool,
+}
+
+static INDEXES: LazyLock<RwLock<Vec<RepoIndex>>> = LazyLock::new(|| RwLock::new(Vec::new()));
+static WATCHER: LazyLock<Mutex<Option<RecommendedWatcher>>> = LazyLock::new(|| Mutex::new(None));
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FindResult {
+    repo: String,
+    path: String,
+    score: i64,
+    /// Char offsets into `path` of the matched query characters.
+    positions: Vec<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FindResponse {
+    results: Vec<FindResult>,
+    indexed_files: usize,
+}
+
+fn child_prefix(rel_dir: &str) -> String {
+    if rel_dir.is_empty() {
+        String::new()
+    } else {
+        format!("{rel_dir}/")
+    }
+}
+
+fn join_rel(root: &Path, rel: &str) -> PathBuf {
+    if rel.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(rel)
+    }
+}
+
+/// Walks `start` (inside `root`) honoring .gitignore, .git/info/exclude and
+/// parent ignore files. `start` itself is not reported. The flag is set when
+/// the walk stopped at `MAX_INDEXED_FILES`.
+fn walk(root: &Path, start: &Path, max_depth: Option<usize>) -> (Vec<String>, Vec<String>, bool) {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut truncated = false;
+
+    let walker = WalkBuilder::new(start)
+        .hidden(false)
+        .parents(true)
+        .max_depth(max_depth)
+        .filter_entry(|e| e.file_name() != ".git")
+        .build();
+
+    for entry in walker.flatten() {
+        if entry.depth() == 0 {
+            continue;
+        }
+        let Ok(rel) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let rel = rel.to_string_lossy().into_owned();
+        match entry.file_type() {
+            Some(ft) if ft.is_dir() => dirs.push(rel),
+            Some(_) => {
+                if files.len() >= MAX_INDEXED_FILES {
+                    eprintln!(
+                        "file index: {} has over {MAX_INDEXED_FILES} files, truncating",
+                        root.display()
+                    );
+                    truncated = true;
+                    break;
+                }
+                files.push(rel);
+            }
+            None => {}
+        }
+    }
+
+    (files, dirs, truncated)
+}
+
+/// Watches each of `dirs`, carrying on past failures (typically the inotify
+/// watch limit). Returns whether every watch was set up.
+fn watch_dirs(root: &Path, dirs: &[String]) -> bool {
+    let mut guard = WATCHER.lock().unwrap();
+    let Some(watcher) = guard.as_mut() else {
+        return false;
+    };
+    let mut failed = 0;
+    for rel in dirs {
+        if let Err(e) = watcher.watch(&join_rel(root, rel), RecursiveMode::NonRecursive) {
+            if failed == 0 {
+                eprintln!("file index: failed to watch {}/{rel}: {e}", root.display());
+            }
+            failed += 1;
+        }
+    }
+    if failed > 1 {
+        eprintln!(
+            "file index: {failed} directories under {} are not watched",
+            root.display()
+        );
+    }
+    failed == 0
+}
+
+fn build_index(clone_path: &str) -> Option<RepoIndex> {
+    let root = PathBuf::from(full_path(clone_path));
+    let root = std::fs::canonicalize(root).ok()?;
+    if !root.is_dir() {
+        return None;
+    }
+
+    let (files, mut dirs, truncated) = walk(&root, &root, None);
+    dirs.push(String::new());
+    let watched = watch_dirs(&root, &dirs);
+
+    Some(RepoIndex {
+        clone_path: clone_path.to_string(),
+        root,
+        files: files.into_iter().collect(),
+        dirs: dirs.into_iter().collect(),
+        complete: watched && !truncated,
+    })
+}
+
+/// Builds the index for every configured repo that exists on disk and isn't
+/// indexed yet. Repos are usually cloned after the agent boots, so this runs
+/// on demand rather than only at startup.
+pub(crate) fn ensure_indexes() {
+    let Some(cfg) = get_config() else {
+        return;
+    };
+    let missing: Vec<String> = {
+        let indexes = INDEXES.read().unwrap();
+        cfg.repos
+            .iter()
+            .map(|r| r.clone_path.clone())
+            .filter(|c| !indexes.iter().any(|i| &i.clone_path == c))
+            .collect()
+    };
+    for clone_path in missing {
+        if let Some(index) = build_index(&clone_path) {
+            let mut indexes = INDEXES.write().unwrap();
+            if !indexes.iter().any(|i| i.clone_path == clone_path) {
+                indexes.push(index);
+            }
+        }
+    }
+}
+
+impl RepoIndex {
+    fn remove_subtree(&mut self, rel_dir: &str) {
+        let prefix = child_prefix(rel_dir);
+        self.files.retain(|f| !f.starts_with(&prefix));
+        self.dirs.retain(|d| !d.starts_with(&prefix));
+        self.dirs.remove(rel_dir);
+    }
+
+    fn direct_children<'a>(set: &'a BTreeSet<String>, rel_dir: &str) -> Vec<&'a String> {
+        let prefix = child_prefix(rel_dir);
+        set.range(prefix.clone()..)
+            .take_while(|p| p.starts_with(&prefix))
+            .filter(|p| p.len() > prefix.len() && !p[prefix.len()..].contains('/'))
+            .collect()
+    }
+
+    /// Re-reads one directory after a change. With `recursive`, the whole
+    /// subtree is rebuilt (an ignore file changed); otherwise only its direct
+    /// entries are, plus a full walk of subdirectories that newly appeared.
+    /// Returns the directories that need a watch.
+    fn relist(&mut self, rel_dir: &str, recursive: bool) -> Vec<String> {
+        let abs = join_rel(&self.root, rel_dir);
+        if !abs.is_dir() {
+            self.remove_subtree(rel_dir);
+            return Vec::new();
+        }
+
+        if recursive {
+            self.remove_subtree(rel_dir);
+            let (files, mut dirs, truncated) = walk(&self.root, &abs, None);
+            self.complete &= !truncated;
+            dirs.push(rel_dir.to_string());
+            self.files.extend(files);
+            self.dirs.extend(dirs.iter().cloned());
+            return dirs;
+        }
+
+        let (files, dirs, truncated) = walk(&self.root, &abs, Some(1));
+        self.complete &= !truncated;
+        let stale: Vec<String> = Self::direct_children(&self.files, rel_dir)
+            .into_iter()
+            .cloned()
+            .collect();
+        for f in stale {
+            self.files.remove(&f);
+        }
+        self.files.extend(files);
+
+        let old_dirs: Vec<String> = Self::direct_children(&self.dirs, rel_dir)
+            .into_iter()
+            .cloned()
+            .collect();
+        for d in old_dirs.iter().filter(|d| !dirs.contains(d)) {
+            self.remove_subtree(d);
+        }
+
+        let mut to_watch = Vec::new();
+        for d in dirs.into_iter().filter(|d| !old_dirs.contains(d)) {
+            to_watch.extend(self.relist(&d, true));
+        }
+        to_watch
+    }
+}
+
+fn apply_events(events: Vec<Event>) {
+    // dir -> whether the whole subtree must be rebuilt
+    let mut dirty: HashMap<PathBuf, bool> = HashMap::new();
+
+    for event in &events {
+        if event.need_rescan() {
+            INDEXES.write().unwrap().clear();
+            ensure_indexes();
+            return;
+        }
+        for path in &event.paths {
+            let is_ignore_file = path
+                .file_name()
+                .is_some_and(|n| n == ".gitignore" || n == ".ignore");
+            let structural = matches!(
+                event.kind,
+                EventKind::Create(_)
+                    | EventKind::Remove(_)
+                    | EventKind::Modify(ModifyKind::Name(_))
+                    | EventKind::Any
+                    | EventKind::Other
+            );
+            if !structural && !is_ignore_file {
+                continue;
+            }
+            let Some(parent) = path.parent() else {
+                continue;
+            };
+            let recursive = dirty.entry(parent.to_path_buf()).or_insert(false);
+            *recursive |= is_ignore_file;
+            // The watched directory itself going away shows up as an event
+            // on its own path; re-list it so its subtree is dropped.
+            if matches!(event.kind, EventKind::Remove(_)) {
+                dirty.entry(path.clone()).or_insert(false);
+            }
+        }
+    }
+
+    let mut watches: Vec<(PathBuf, Vec<String>)> = Vec::new();
+    {
+        let mut indexes = INDEXES.write().unwrap();
+        for (dir, recursive) in dirty {
+            let Some(index) = indexes.iter_mut().find(|i| dir.starts_with(&i.root)) else {
+                continue;
+            };
+            let Ok(rel) = dir.strip_prefix(&index.root) else {
+                continue;
+            };
+            let rel = rel.to_string_lossy().into_owned();
+            // Events in ignored directories have no indexed parent.
+            if !index.dirs.contains(&rel) {
+                continue;
+            }
+            let new_dirs = index.relist(&rel, recursive);
+            if !new_dirs.is_empty() {
+                watches.push((index.root.clone(), new_dirs));
+            }
+        }
+        // A removed repo root: forget it so the next query rebuilds it
+        // (e.g. after a re-clone).
+        indexes.retain(|i| i.dirs.contains(""));
+    }
+
+    for (root, dirs) in watches {
+        if !watch_dirs(&root, &dirs) {
+            let mut indexes = INDEXES.write().unwrap();
+            if let Some(index) = indexes.iter_mut().find(|i| i.root == root) {
+                index.complete = false;
+            }
+        }
+    }
+}
+
+/// Whether the repo at `root` (canonical) is indexed with every directory
+/// watched, so its worktree changes are all seen.
+pub(crate) fn fully_watched(root: &Path) -> bool {
+    INDEXES
+        .read()
+        .unwrap()
+        .iter()
+        .any(|i| i.root == root && i.complete)
+}
+
+pub async fn start() {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            let _ = tx.send(event);
+        }
+    });
+    match watcher {
+        Ok(w) => *WATCHER.lock().unwrap() = Some(w),
+        Err(e) => eprintln!("file index: watcher unavailable, index will not update: {e}"),
+    }
+
+    let _ = tokio::task::spawn_blocking(ensure_indexes).await;
+
+    while let Some(first) = rx.recv().await {
+        let mut batch = vec![first];
+        tokio::time::sleep(EVENT_DEBOUNCE).await;
+        while let Ok(event) = rx.try_recv() {
+            batch.push(event);
+        }
+        crate::routes::git::invalidate_status(&batch);
+        let _ = tokio::task::spawn_blocking(move || apply_events(batch)).await;
+    }
+}
+
+fn is_boundary(prev: char, cur: char) -> bool {
+    matches!(prev, '/' | '_' | '-' | '.' | ' ') || (prev.is_lowercase() && cur.is_uppercase())
+}
+
+/// fzf-style subsequence match: find the shortest window ending at the
+/// earliest full match, then score the greedy positions inside it. Returns
+/// `None` when `query` (already lowercased) isn't a subsequence of `path`.
+fn fuzzy_match(query: &[char], path: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = path.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    let mut qi = 0;
+    let mut end = 0;
+    for (i, c) in lower.iter().enumerate() {
+        if *c == query[qi] {
+            qi += 1;
+            if qi == query.len() {
+                end = i;
+                break;
+            }
+        }
+    }
+    if qi < query.len() {
+        return None;
+    }
+
+    let mut qi = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if lower[i] == query[qi - 1] {
+            qi -= 1;
+            if qi == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut qi = 0;
+    for (i, c) in lower.iter().enumerate().take(end + 1).skip(start) {
+        if qi < query.len() && *c == query[qi] {
+            positions.push(i);
+            qi += 1;
+        }
+    }
+
+    let basename_start = chars.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    let mut score: i64 = 0;
+    for (n, &pos) in positions.iter().enumerate() {
+        score += 16;
+        if pos == 0 || is_boundary(chars[pos - 1], chars[pos]) {
+            score += if pos == 0 || chars[pos - 1] == '/' { 10 } else { 8 };
+        }
+        if n > 0 {
+            let gap = pos - positions[n - 1] - 1;
+            if gap == 0 {
+                score += 6;
+            } else {
+                score -= (gap as i64).min(8);
+            }
+        }
+    }
+    if positions[0] >= basename_start {
+        score += 24;
+    }
+    score -= (chars.len() / 16) as i64;
+
+    Some((score, positions))
+}
+
+fn search(query: &str, repo: Option<&str>, limit: usize) -> FindResponse {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    let indexes = INDEXES.read().unwrap();
+    let mut indexed_files = 0;
+    let mut results = Vec::new();
+
+    for index in indexes.iter() {
+        if repo.is_some_and(|r| r != index.clone_path) {
+            continue;
+        }
+        indexed_files += index.files.len();
+        if query.is_empty() {
+            continue;
+        }
+        for path in &index.files {
+            if let Some((score, positions)) = fuzzy_match(&query, path) {
+                results.push(FindResult {
+                    repo: index.clone_path.clone(),
+                    path: path.clone(),
+                    score,
+                    positions,
+                });
+            }
+        }
+    }
+
+    results.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then(a.path.len().cmp(&b.path.len()))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    results.truncate(limit);
+
+    FindResponse {
+        results,
+        indexed_files,
+    }
+}
+
+pub async fn handle_find_files(query: &str) -> Response<Full<Bytes>> {
+    let mut q = String::new();
+    let mut repo: Option<String> = None;
+    let mut limit = DEFAULT_FIND_LIMIT;
+
+    for param in query.split('&') {
+        let mut kv = param.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("q"), Some(v)) => {
+                q = urlencoding::decode(&v.replace('+', " "))
+                    .unwrap_or_default()
+                    .into_owned()
+            }
+            (Some("repo"), Some(v)) => {
+                repo = Some(urlencoding::decode(v).unwrap_or_default().into_owned())
+            }
+            (Some("limit"), Some(v)) => limit = v.parse().unwrap_or(DEFAULT_FIND_LIMIT),
+            _ => {}
+        }
+    }
+    let limit = limit.clamp(1, MAX_FIND_LIMIT);
+
+    let resp = tokio::task::spawn_blocking(move || {
+        ensure_indexes();
+        search(&q, repo.as_deref(), limit)
+    })
+    .await;
+
+    match resp {
+        Ok(r) => json_ok(serde_json::to_value(r).unwrap()),
+        Err(_) => json_ok(serde_json::json!({"results": [], "indexedFiles": 0})),
+    }
+}
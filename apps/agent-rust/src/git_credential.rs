@@ -0,0 +1,273 @@
+// Git credential helper backed by the manager.
+//
+// git runs `sandbox-agent git-credential <op>`: the manager registers it in
+// the system gitconfig, and the git routes also pass it per command (see
+// `helper_env`). That short-lived process forwards the request
+// over loopback to the running agent, which asks the manager for a
+// short-lived token and caches it in memory until shortly before it expires.
+// Tokens never touch the disk: `store` is a no-op and `erase` only drops the
+// cached entry.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::net::SocketAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+
+use crate::body::{ReadBodyError, read_body_limited};
+use crate::config::{AGENT_PORT, get_config};
+use crate::limits::MAX_REQUEST_BODY_BYTES;
+use crate::response::{json_error, json_ok};
+
+const MANAGER_TIMEOUT: Duration = Duration::from_secs(10);
+// Used when the manager doesn't say how long a token lives.
+const DEFAULT_TOKEN_TTL_SECS: u64 = 300;
+// Tokens are dropped this long before they expire, so git never starts a
+// push with a token that dies mid-transfer.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+struct CachedCredential {
+    username: String,
+    password: String,
+    expires_at: Instant,
+}
+
+static CREDENTIALS: LazyLock<Mutex<HashMap<String, CachedCredential>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Request attributes from the git credential protocol, plus the operation.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CredentialRequest {
+    #[serde(default)]
+    operation: String,
+    #[serde(default)]
+    protocol: String,
+    #[serde(default)]
+    host: String,
+    #[serde(default)]
+    path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CredentialResponse {
+    username: String,
+    password: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+impl CredentialRequest {
+    fn cache_key(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{}://{}/{}", self.protocol, self.host, path),
+            None => format!("{}://{}", self.protocol, self.host),
+        }
+    }
+}
+
+/// Environment that registers the helper for one git invocation, on top of
+/// any helpers dev configured. Empty when the sandbox has no manager URL.
+pub fn helper_env() -> Vec<(&'static str, String)> {
+    if get_config().is_none_or(|c| c.network.manager_internal_url.is_empty()) {
+        return Vec::new();
+    }
+    let exe = std::env::current_exe()
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "/usr/local/bin/sandbox-agent".to_string());
+    vec![
+        ("GIT_CONFIG_COUNT", "1".to_string()),
+        ("GIT_CONFIG_KEY_0", "credential.helper".to_string()),
+        ("GIT_CONFIG_VALUE_0", format!("!'{exe}' git-credential")),
+    ]
+}
+
+async fn post_json(
+    uri: &Uri,
+    headers: &[(&str, &str)],
+    body: Vec<u8>,
+) -> Result<(StatusCode, Bytes), String> {
+    if uri.scheme_str() != Some("http") {
+        return Err(format!("Unsupported URL scheme: {uri}"));
+    }
+    let host = uri.host().ok_or("URL has no host")?;
+    let port = uri.port_u16().unwrap_or(80);
+
+    let stream = TcpStream::connect((host, port))
+        .await
+        .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::spawn(conn);
+
+    let mut req = Request::builder()
+        .method(Method::POST)
+        .uri(uri.path_and_query().map_or("/", |p| p.as_str()))
+        .header("host", host)
+        .header("content-type", "application/json");
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let req = req
+        .body(Full::new(Bytes::from(body)))
+        .map_err(|e| e.to_string())?;
+
+    let resp = sender.send_request(req).await.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let body = resp
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    Ok((status, body))
+}
+
+async fn fetch_from_manager(req: &CredentialRequest) -> Result<CredentialResponse, String> {
+    let config = get_config().ok_or("Sandbox config not loaded")?;
+    let base = config.network.manager_internal_url.trim_end_matches('/');
+    let uri: Uri = format!("{base}/git/credentials")
+        .parse()
+        .map_err(|_| format!("Invalid manager URL: {base}"))?;
+
+    let body = serde_json::to_vec(&serde_json::json!({
+        "sandboxId": config.sandbox_id,
+        "protocol": req.protocol,
+        "host": req.host,
+        "path": req.path,
+    }))
+    .unwrap_or_default();
+
+    let (status, body) = tokio::time::timeout(
+        MANAGER_TIMEOUT,
+        post_json(&uri, &[("x-atelier-sandbox-id", &config.sandbox_id)], body),
+    )
+    .await
+    .map_err(|_| "Timed out waiting for the manager".to_string())??;
+    if !status.is_success() {
+        return Err(format!("Manager returned {status}"));
+    }
+    serde_json::from_slice(&body).map_err(|_| "Invalid credential response".to_string())
+}
+
+async fn get_credential(req: &CredentialRequest) -> Result<CredentialResponse, String> {
+    let key = req.cache_key();
+    if let Some(cached) = CREDENTIALS.lock().unwrap().get(&key)
+        && cached.expires_at > Instant::now()
+    {
+        return Ok(CredentialResponse {
+            username: cached.username.clone(),
+            password: cached.password.clone(),
+            expires_in: None,
+        });
+    }
+
+    let fetched = fetch_from_manager(req).await?;
+    let ttl = Duration::from_secs(fetched.expires_in.unwrap_or(DEFAULT_TOKEN_TTL_SECS));
+    if let Some(lifetime) = ttl.checked_sub(EXPIRY_MARGIN) {
+        CREDENTIALS.lock().unwrap().insert(
+            key,
+            CachedCredential {
+                username: fetched.username.clone(),
+                password: fetched.password.clone(),
+                expires_at: Instant::now() + lifetime,
+            },
+        );
+    }
+    Ok(fetched)
+}
+
+/// `POST /git/credential`, called by the helper process. Loopback only: the
+/// agent port is reachable from the cluster, tokens must not be.
+pub async fn handle_credential_request(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    let from_loopback = req
+        .extensions()
+        .get::<SocketAddr>()
+        .is_some_and(|addr| addr.ip().is_loopback());
+    if !from_loopback {
+        return json_error(StatusCode::FORBIDDEN, "Forbidden");
+    }
+
+    let body = match read_body_limited(req, MAX_REQUEST_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(ReadBodyError::TooLarge) => {
+            return json_error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large");
+        }
+        Err(ReadBodyError::ReadFailed) => {
+            return json_error(StatusCode::BAD_REQUEST, "Failed to read body");
+        }
+    };
+    let cred_req: CredentialRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &format!("Invalid JSON: {}", e)),
+    };
+
+    match cred_req.operation.as_str() {
+        "get" => match get_credential(&cred_req).await {
+            Ok(cred) => json_ok(serde_json::to_value(cred).unwrap()),
+            Err(e) => json_error(StatusCode::BAD_GATEWAY, &e),
+        },
+        "erase" => {
+            CREDENTIALS.lock().unwrap().remove(&cred_req.cache_key());
+            json_ok(serde_json::json!({}))
+        }
+        _ => json_ok(serde_json::json!({})),
+    }
+}
+
+/// Entry point for `sandbox-agent git-credential <op>`. Reads the request
+/// from stdin and, for `get`, prints the credential for git. Any failure
+/// prints nothing, so git moves on to its next helper or prompt.
+pub async fn run_helper(operation: &str) -> i32 {
+    let mut req = CredentialRequest {
+        operation: operation.to_string(),
+        ..Default::default()
+    };
+    for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+        if line.is_empty() {
+            break;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key {
+            "protocol" => req.protocol = value.to_string(),
+            "host" => req.host = value.to_string(),
+            "path" => req.path = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    if operation == "store" || !matches!(req.protocol.as_str(), "https" | "http") {
+        return 0;
+    }
+
+    let uri: Uri = format!("http://127.0.0.1:{AGENT_PORT}/git/credential")
+        .parse()
+        .expect("static URI");
+    let body = serde_json::to_vec(&req).unwrap_or_default();
+    let Ok((status, body)) = post_json(&uri, &[], body).await else {
+        return 0;
+    };
+    if operation != "get" || !status.is_success() {
+        return 0;
+    }
+    let Ok(cred) = serde_json::from_slice::<CredentialResponse>(&body) else {
+        return 0;
+    };
+
+    let mut out = std::io::stdout().lock();
+    let _ = writeln!(out, "username={}", cred.username);
+    let _ = writeln!(out, "password={}", cred.password);
+    0
+}
@@ -8,6 +8,11 @@ pub const MAX_COMMAND_OUTPUT_BYTES: usize = 15 * 1024 * 1024;
 pub const MAX_CONCURRENT_EXEC: usize = 8;
 pub const MAX_CONCURRENT_GIT: usize = 4;
 pub const MAX_CONCURRENT_FILES: usize = 4;
+// Clones run for minutes, so they get their own slots instead of holding
+// GIT_SEMAPHORE's.
+pub const MAX_CONCURRENT_CLONES: usize = 2;
+// Likewise for fetch, pull and push, which wait on the remote.
+pub const MAX_CONCURRENT_GIT_NETWORK: usize = 4;
 
 pub static EXEC_SEMAPHORE: LazyLock<Semaphore> =
     LazyLock::new(|| Semaphore::new(MAX_CONCURRENT_EXEC));
@@ -15,3 +20,7 @@ pub static GIT_SEMAPHORE: LazyLock<Semaphore> =
     LazyLock::new(|| Semaphore::new(MAX_CONCURRENT_GIT));
 pub static FILES_SEMAPHORE: LazyLock<Semaphore> =
     LazyLock::new(|| Semaphore::new(MAX_CONCURRENT_FILES));
+pub static CLONE_SEMAPHORE: LazyLock<Semaphore> =
+    LazyLock::new(|| Semaphore::new(MAX_CONCURRENT_CLONES));
+pub static GIT_NETWORK_SEMAPHORE: LazyLock<Semaphore> =
+    LazyLock::new(|| Semaphore::new(MAX_CONCURRENT_GIT_NETWORK));
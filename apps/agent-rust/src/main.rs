@@ -1,8 +1,11 @@
 mod body;
 mod command;
 mod config;
+mod file_index;
 mod forwarder;
+mod git_credential;
 mod limits;
+mod path_policy;
 mod response;
 mod router;
 mod routes;
@@ -26,8 +29,10 @@ pub fn utc_rfc3339() -> String {
     let dur = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap_or_default();
-    let secs = dur.as_secs();
+    rfc3339_from_secs(dur.as_secs())
+}
 
+pub fn rfc3339_from_secs(secs: u64) -> String {
     let days = secs / 86400;
     let rem = secs % 86400;
     let hours = rem / 3600;
@@ -66,6 +71,13 @@ async fn handle(req: Request<hyper::body::Incoming>) -> Result<Response<Full<Byt
 
 #[tokio::main]
 async fn main() {
+    // git invokes the binary itself as its credential helper.
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("git-credential") {
+        let op = args.get(2).map_or("get", String::as_str);
+        std::process::exit(git_credential::run_helper(op).await);
+    }
+
     println!("Sandbox agent starting...");
 
     watchdog::start();
@@ -89,6 +101,9 @@ async fn main() {
         terminal::ensure_terminal_from_config().await;
     });
 
+    tokio::spawn(file_index::start());
+    tokio::spawn(routes::git::start_status_cache());
+
     let (dev_listen, dev_target) = config::get_config()
         .and_then(|c| c.dev_forwarder)
         .map_or((DEV_PORT, DEV_APP_PORT), |f| (f.public_port, f.app_port));
@@ -97,7 +112,7 @@ async fn main() {
     });
 
     loop {
-        let (stream, _addr) = match listener.accept().await {
+        let (stream, addr) = match listener.accept().await {
             Ok(conn) => conn,
             Err(e) => {
                 eprintln!("TCP accept error: {e}");
@@ -107,12 +122,18 @@ async fn main() {
         tokio::spawn(async move {
             let io = TokioIo::new(stream);
             if let Err(e) = http1::Builder::new()
-                .serve_connection(io, service_fn(handle))
+                .serve_connection(
+                    io,
+                    service_fn(move |mut req| {
+                        // Lets loopback-only routes check the peer.
+                        req.extensions_mut().insert(addr);
+                        handle(req)
+                    }),
+                )
                 .await
+                && !e.is_incomplete_message()
             {
-                if !e.is_incomplete_message() {
-                    eprintln!("connection error: {e}");
-                }
+                eprintln!("connection error: {e}");
             }
         });
     }
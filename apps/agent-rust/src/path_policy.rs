@@ -0,0 +1,151 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::{CONFIG_PATH, get_config};
+
+// Used when config.json carries no `pathPolicy` (pre-existing blobs): the
+// manager still writes to /etc/profile.d, /etc/gitconfig and friends, so the
+// whole filesystem stays writable and only the protected list applies.
+const DEFAULT_WRITABLE_ROOTS: &[&str] = &["/"];
+
+// Always protected, on top of whatever the config adds. Overwriting the
+// config or the agent binary would let a single bad write take over the
+// sandbox on the next boot.
+const BUILTIN_PROTECTED_PATHS: &[&str] = &[
+    CONFIG_PATH,
+    "/usr/local/bin/sandbox-agent",
+    "/proc",
+    "/sys",
+    "/dev",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathAccess {
+    Read,
+    Write,
+}
+
+struct PathPolicy {
+    writable_roots: Vec<PathBuf>,
+    protected_paths: Vec<PathBuf>,
+}
+
+fn current_policy() -> PathPolicy {
+    let configured = get_config().and_then(|c| c.path_policy);
+
+    let writable_roots: Vec<PathBuf> = match &configured {
+        Some(p) if !p.writable_roots.is_empty() => {
+            p.writable_roots.iter().map(PathBuf::from).collect()
+        }
+        _ => DEFAULT_WRITABLE_ROOTS.iter().map(PathBuf::from).collect(),
+    };
+
+    let mut protected_paths: Vec<PathBuf> =
+        BUILTIN_PROTECTED_PATHS.iter().map(PathBuf::from).collect();
+    if let Ok(exe) = std::env::current_exe() {
+        protected_paths.push(exe);
+    }
+    if let Some(p) = &configured {
+        protected_paths.extend(p.protected_paths.iter().map(PathBuf::from));
+    }
+
+    // Compare canonical forms so a symlinked root (e.g. /home/dev -> /data/dev)
+    // still matches the resolved request path. A root that can't be resolved
+    // only narrows what is writable. A protected path that can't be (a
+    // dangling symlink, so nothing is there yet) is protected as configured.
+    let protected_paths = protected_paths
+        .into_iter()
+        .map(|p| resolve_symlinks(&p).unwrap_or(p))
+        .collect();
+    PathPolicy {
+        writable_roots: writable_roots
+            .iter()
+            .filter_map(|p| resolve_symlinks(p).ok())
+            .collect(),
+        protected_paths,
+    }
+}
+
+fn normalize_lexically(path: &str) -> Result<PathBuf, String> {
+    let raw = Path::new(path);
+    if !raw.is_absolute() {
+        return Err(format!("Path must be absolute: {path}"));
+    }
+
+    let mut out = PathBuf::from("/");
+    for component in raw.components() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::Normal(part) => out.push(part),
+            Component::ParentDir => {
+                return Err(format!("Path must not contain '..': {path}"));
+            }
+            Component::Prefix(_) => return Err(format!("Unsupported path: {path}")),
+        }
+    }
+    Ok(out)
+}
+
+/// Resolves symlinks in the longest existing prefix of `path` and re-appends
+/// the components that don't exist yet, so paths about to be created can be
+/// checked against where they will actually land.
+fn resolve_symlinks(path: &Path) -> Result<PathBuf, String> {
+    let mut existing = path.to_path_buf();
+    let mut missing: Vec<OsString> = Vec::new();
+
+    loop {
+        match fs::canonicalize(&existing) {
+            Ok(mut resolved) => {
+                for part in missing.iter().rev() {
+                    resolved.push(part);
+                }
+                return Ok(resolved);
+            }
+            Err(_) => {
+                // A dangling symlink would be followed on write and create its
+                // target wherever it points, so refuse instead of skipping it.
+                if fs::symlink_metadata(&existing).is_ok() {
+                    return Err(format!(
+                        "Path resolves through a dangling symlink: {}",
+                        existing.display()
+                    ));
+                }
+                match existing.file_name() {
+                    Some(name) => {
+                        missing.push(name.to_os_string());
+                        existing.pop();
+                    }
+                    None => return Ok(path.to_path_buf()),
+                }
+            }
+        }
+    }
+}
+
+/// Validates a client-supplied path against the sandbox path policy and
+/// returns its symlink-free form, which callers must use for the actual I/O.
+pub fn resolve(path: &str, access: PathAccess) -> Result<PathBuf, String> {
+    let normalized = normalize_lexically(path)?;
+    let resolved = resolve_symlinks(&normalized)?;
+    let policy = current_policy();
+
+    if policy
+        .protected_paths
+        .iter()
+        .any(|p| resolved.starts_with(p))
+    {
+        return Err(format!("Path is protected: {path}"));
+    }
+
+    if access == PathAccess::Write
+        && !policy
+            .writable_roots
+            .iter()
+            .any(|root| resolved.starts_with(root))
+    {
+        return Err(format!("Path is outside the writable roots: {path}"));
+    }
+
+    Ok(resolved)
+}
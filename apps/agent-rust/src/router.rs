@@ -2,6 +2,8 @@ use http_body_util::Full;
 use hyper::body::Bytes;
 use hyper::{Method, Request, Response, StatusCode};
 
+use crate::file_index;
+use crate::git_credential;
 use crate::response::json_error;
 use crate::routes;
 use crate::terminal;
@@ -17,11 +19,73 @@ pub async fn route(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>>
         (Method::POST, "/exec/batch") => routes::exec::handle_exec_batch(req).await,
 
         (Method::POST, "/git/status") => routes::git::handle_git_status(req).await,
+        (Method::GET, "/git/events") => {
+            routes::git::handle_git_events(req.uri().query().unwrap_or("")).await
+        }
         (Method::POST, "/git/diff") => routes::git::handle_git_diff(req).await,
+        (Method::POST, "/git/diff/content") => routes::git::handle_git_diff_content(req).await,
+        (Method::POST, "/git/log") => routes::git::handle_git_log(req).await,
+        (Method::POST, "/git/blame") => routes::git::handle_git_blame(req).await,
+        (Method::POST, "/git/compare") => routes::git::handle_git_compare(req).await,
+        (Method::POST, "/git/checks") => routes::git::handle_git_checks(req).await,
         (Method::POST, "/git/commit") => routes::git::handle_git_commit(req).await,
         (Method::POST, "/git/push") => routes::git::handle_git_push(req).await,
+        (Method::POST, "/git/stash") => routes::git::handle_git_stash_list(req).await,
+        (Method::POST, "/git/stash/push") => routes::git::handle_git_stash_push(req).await,
+        (Method::POST, "/git/stash/show") => routes::git::handle_git_stash_show(req).await,
+        (Method::POST, "/git/stash/apply") => routes::git::handle_git_stash_apply(req).await,
+        (Method::POST, "/git/stash/pop") => routes::git::handle_git_stash_pop(req).await,
+        (Method::POST, "/git/stash/drop") => routes::git::handle_git_stash_drop(req).await,
+        (Method::POST, "/git/checkpoint") => routes::git::handle_git_checkpoint(req).await,
+        (Method::POST, "/git/checkpoint/list") => {
+            routes::git::handle_git_checkpoint_list(req).await
+        }
+        (Method::POST, "/git/checkpoint/diff") => {
+            routes::git::handle_git_checkpoint_diff(req).await
+        }
+        (Method::POST, "/git/checkpoint/restore") => {
+            routes::git::handle_git_checkpoint_restore(req).await
+        }
+        (Method::POST, "/git/checkpoint/prune") => {
+            routes::git::handle_git_checkpoint_prune(req).await
+        }
+        (Method::POST, "/git/conflicts") => routes::git::handle_git_conflicts(req).await,
+        (Method::POST, "/git/conflicts/resolve") => {
+            routes::git::handle_git_conflicts_resolve(req).await
+        }
+        (Method::POST, "/git/conflicts/continue") => {
+            routes::git::handle_git_conflicts_continue(req).await
+        }
+        (Method::POST, "/git/conflicts/abort") => {
+            routes::git::handle_git_conflicts_abort(req).await
+        }
+        (Method::POST, "/git/worktrees") => routes::git::handle_git_worktrees(req).await,
+        (Method::POST, "/git/worktrees/add") => routes::git::handle_git_worktree_add(req).await,
+        (Method::POST, "/git/worktrees/remove") => {
+            routes::git::handle_git_worktree_remove(req).await
+        }
+        (Method::POST, "/git/worktrees/prune") => routes::git::handle_git_worktree_prune(req).await,
+        (Method::POST, "/git/credential") => {
+            git_credential::handle_credential_request(req).await
+        }
+        (Method::POST, "/git/clone") => routes::git::handle_git_clone(req).await,
+        (Method::POST, "/git/fetch") => routes::git::handle_git_fetch(req).await,
+        (Method::POST, "/git/pull") => routes::git::handle_git_pull(req).await,
+        (Method::POST, "/git/branches") => routes::git::handle_git_branches(req).await,
+        (Method::POST, "/git/branches/create") => routes::git::handle_git_branch_create(req).await,
+        (Method::POST, "/git/branches/checkout") => {
+            routes::git::handle_git_branch_checkout(req).await
+        }
+        (Method::POST, "/git/branches/rename") => routes::git::handle_git_branch_rename(req).await,
+        (Method::POST, "/git/branches/delete") => routes::git::handle_git_branch_delete(req).await,
 
         (Method::POST, "/files/write") => routes::files::handle_write_files(req).await,
+        (Method::POST, "/files/stat") => routes::files::handle_stat_files(req).await,
+        (Method::POST, "/files/sync/plan") => routes::file_sync::handle_sync_plan(req).await,
+        (Method::POST, "/files/sync/apply") => routes::file_sync::handle_sync_apply(req).await,
+        (Method::GET, "/files/find") => {
+            file_index::handle_find_files(req.uri().query().unwrap_or("")).await
+        }
 
         (Method::GET, "/services") => routes::services::handle_services_list().await,
 
@@ -39,6 +103,14 @@ pub async fn route(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>>
                     };
                 }
             }
+            if let Some(id) = path.strip_prefix("/git/clone/")
+                && method == Method::GET
+                && !id.is_empty()
+                && !id.contains('/')
+            {
+                return routes::git::handle_git_clone_status(id, req.uri().query().unwrap_or(""))
+                    .await;
+            }
             if let Some(rest) = path.strip_prefix("/services/") {
                 let parts: Vec<&str> = rest.splitn(2, '/').collect();
                 if parts.len() == 2 {
@@ -7,6 +7,7 @@ use crate::body::{read_body_limited, ReadBodyError};
 use crate::command::run_shell_command_limited;
 use crate::config::DEFAULT_EXEC_TIMEOUT_MS;
 use crate::limits::{EXEC_SEMAPHORE, MAX_COMMAND_OUTPUT_BYTES, MAX_REQUEST_BODY_BYTES};
+use crate::path_policy::{resolve, PathAccess};
 use crate::response::json_ok;
 
 #[derive(Deserialize)]
@@ -31,6 +32,12 @@ struct BatchBody {
     commands: Vec<BatchCommand>,
 }
 
+fn resolve_workdir(workdir: Option<&str>) -> Result<Option<String>, String> {
+    workdir
+        .map(|dir| resolve(dir, PathAccess::Read).map(|p| p.to_string_lossy().into_owned()))
+        .transpose()
+}
+
 pub async fn handle_exec(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
     let _permit = EXEC_SEMAPHORE.acquire().await.unwrap();
 
@@ -60,13 +67,18 @@ pub async fn handle_exec(req: Request<hyper::body::Incoming>) -> Response<Full<B
         }
     };
 
+    let workdir = match resolve_workdir(parsed.workdir.as_deref()) {
+        Ok(w) => w,
+        Err(e) => return json_ok(serde_json::json!({"exitCode": 1, "stdout": "", "stderr": e})),
+    };
+
     let timeout_ms = parsed.timeout.unwrap_or(DEFAULT_EXEC_TIMEOUT_MS);
     json_ok(
         run_shell_command_limited(
             &parsed.command,
             timeout_ms,
             parsed.user.as_deref(),
-            parsed.workdir.as_deref(),
+            workdir.as_deref(),
             MAX_COMMAND_OUTPUT_BYTES,
         )
         .await,
@@ -94,14 +106,19 @@ pub async fn handle_exec_batch(req: Request<hyper::body::Incoming>) -> Response<
         set.spawn(async move {
             let _permit = EXEC_SEMAPHORE.acquire().await.unwrap();
             let timeout_ms = cmd.timeout.unwrap_or(DEFAULT_EXEC_TIMEOUT_MS);
-            let mut result = run_shell_command_limited(
-                &cmd.command,
-                timeout_ms,
-                cmd.user.as_deref(),
-                cmd.workdir.as_deref(),
-                MAX_COMMAND_OUTPUT_BYTES,
-            )
-            .await;
+            let mut result = match resolve_workdir(cmd.workdir.as_deref()) {
+                Ok(workdir) => {
+                    run_shell_command_limited(
+                        &cmd.command,
+                        timeout_ms,
+                        cmd.user.as_deref(),
+                        workdir.as_deref(),
+                        MAX_COMMAND_OUTPUT_BYTES,
+                    )
+                    .await
+                }
+                Err(e) => serde_json::json!({"exitCode": 1, "stdout": "", "stderr": e}),
+            };
             result
                 .as_object_mut()
                 .expect("json object")
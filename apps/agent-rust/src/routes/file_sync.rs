@@ -0,0 +1,405 @@
+use data_encoding::BASE64;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::body::{read_body_limited, ReadBodyError};
+use crate::limits::{FILES_SEMAPHORE, MAX_REQUEST_BODY_BYTES};
+use crate::path_policy::{resolve, PathAccess};
+use crate::response::{json, json_error, json_ok};
+use crate::routes::files::{
+    read_existing, sha256_bytes, sha256_file, write_content, FileWriteResult,
+};
+
+// Upper bound on files walked under a sync root, so pointing a sync at `/`
+// or a huge node_modules fails fast instead of pinning the agent.
+const MAX_SYNC_ENTRIES: usize = 100_000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPlanRequest {
+    pub root: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPlanFileError {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPlanResponse {
+    pub root: String,
+    pub missing: Vec<String>,
+    pub changed: Vec<String>,
+    pub extraneous: Vec<String>,
+    pub unchanged: usize,
+    /// Entries that couldn't be compared; the rest of the plan still holds.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<SyncPlanFileError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentEncoding {
+    #[default]
+    Utf8,
+    /// For binary files, which JSON strings can't carry as-is.
+    Base64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncUpload {
+    pub path: String,
+    pub content: String,
+    #[serde(default)]
+    pub encoding: ContentEncoding,
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncApplyRequest {
+    pub root: String,
+    #[serde(default)]
+    pub files: Vec<SyncUpload>,
+    #[serde(default)]
+    pub delete: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDeleteResult {
+    pub path: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncApplyResponse {
+    pub root: String,
+    pub results: Vec<FileWriteResult>,
+    pub deleted: Vec<SyncDeleteResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Normalizes a manifest path relative to the sync root, refusing anything
+/// that could step outside of it lexically.
+fn normalize_relative(rel: &str) -> Result<String, String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for component in Path::new(rel).components() {
+        match component {
+            Component::Normal(part) => parts.push(
+                part.to_str()
+                    .ok_or_else(|| format!("Path is not valid UTF-8: {rel}"))?,
+            ),
+            Component::CurDir => {}
+            _ => return Err(format!("Path must be relative and must not contain '..': {rel}")),
+        }
+    }
+    if parts.is_empty() {
+        return Err(format!("Empty path: {rel:?}"));
+    }
+    Ok(parts.join("/"))
+}
+
+/// Resolves `rel` under `root` through the path policy and checks that
+/// symlinks inside the tree don't lead outside of it.
+fn resolve_within(root: &Path, rel: &str, access: PathAccess) -> Result<PathBuf, String> {
+    let joined = root.join(rel);
+    let resolved = resolve(&joined.to_string_lossy(), access)?;
+    if !resolved.starts_with(root) {
+        return Err(format!("Path escapes the sync root: {rel}"));
+    }
+    Ok(resolved)
+}
+
+/// Lists regular files under `root` as relative paths. Symlinks are not
+/// followed, so a link to a directory elsewhere can't pull it into the sync.
+fn walk_files(root: &Path) -> Result<HashSet<String>, String> {
+    let mut files = HashSet::new();
+    let mut stack = vec![root.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                stack.push(path);
+            } else if file_type.is_file() {
+                if files.len() >= MAX_SYNC_ENTRIES {
+                    return Err(format!(
+                        "Sync root has more than {MAX_SYNC_ENTRIES} files"
+                    ));
+                }
+                if let Ok(rel) = path.strip_prefix(root) {
+                    files.insert(rel.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+fn plan_sync(req: SyncPlanRequest) -> SyncPlanResponse {
+    let mut plan = SyncPlanResponse {
+        root: req.root.clone(),
+        ..Default::default()
+    };
+
+    let result = (|| -> Result<(), String> {
+        let root = resolve(&req.root, PathAccess::Read)?;
+        let mut local = walk_files(&root)?;
+
+        for entry in &req.entries {
+            let rel = match normalize_relative(&entry.path) {
+                Ok(rel) => rel,
+                Err(error) => {
+                    plan.errors.push(SyncPlanFileError {
+                        path: entry.path.clone(),
+                        error,
+                    });
+                    continue;
+                }
+            };
+            if !local.remove(&rel) {
+                if fs::symlink_metadata(root.join(&rel)).is_ok() {
+                    plan.changed.push(rel);
+                } else {
+                    plan.missing.push(rel);
+                }
+                continue;
+            }
+
+            let path = root.join(&rel);
+            let same_size = fs::metadata(&path).is_ok_and(|m| m.len() == entry.size);
+            if !same_size {
+                plan.changed.push(rel);
+                continue;
+            }
+            match sha256_file(&path) {
+                Ok(hash) if hash.eq_ignore_ascii_case(&entry.sha256) => plan.unchanged += 1,
+                Ok(_) => plan.changed.push(rel),
+                Err(error) => plan.errors.push(SyncPlanFileError { path: rel, error }),
+            }
+        }
+
+        plan.extraneous = local.into_iter().collect();
+        plan.extraneous.sort();
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        plan.error = Some(e);
+    }
+    plan
+}
+
+fn apply_upload(root: &Path, upload: &SyncUpload) -> Result<bool, String> {
+    let rel = normalize_relative(&upload.path)?;
+    let target = resolve_within(root, &rel, PathAccess::Write)?;
+
+    let content = match upload.encoding {
+        ContentEncoding::Utf8 => upload.content.clone().into_bytes(),
+        ContentEncoding::Base64 => BASE64
+            .decode(upload.content.as_bytes())
+            .map_err(|e| format!("Invalid base64 content: {}", e))?,
+    };
+
+    if let Some(expected) = &upload.sha256 {
+        let actual = sha256_bytes(&content);
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(format!("Checksum mismatch: expected {expected}, got {actual}"));
+        }
+    }
+
+    let existing = read_existing(&target)?;
+    let next = Some(content).filter(|c| existing.as_ref() != Some(c));
+    write_content(&target, next, upload.mode.as_deref(), upload.owner.as_deref())
+}
+
+fn apply_delete(root: &Path, rel: &str) -> Result<(), String> {
+    let rel = normalize_relative(rel)?;
+    let lexical = Path::new(&rel);
+    let (Some(parent), Some(name)) = (lexical.parent(), lexical.file_name()) else {
+        return Err(format!("Invalid path: {rel}"));
+    };
+
+    // Resolve only the parent: the entry itself may be a symlink, and it's
+    // the link that gets removed, never its target.
+    let parent = if parent.as_os_str().is_empty() {
+        root.to_path_buf()
+    } else {
+        resolve_within(root, &parent.to_string_lossy(), PathAccess::Write)?
+    };
+    let target = parent.join(name);
+    resolve(&target.to_string_lossy(), PathAccess::Write)?;
+
+    match fs::symlink_metadata(&target) {
+        Ok(m) if m.is_dir() => return Err(format!("Refusing to delete a directory: {rel}")),
+        Ok(_) => fs::remove_file(&target).map_err(|e| format!("Failed to delete: {}", e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to stat: {}", e)),
+    }
+
+    // Drop directories the delete left empty; remove_dir fails on the first
+    // non-empty one, which ends the walk.
+    let mut dir = parent;
+    while dir != root && fs::remove_dir(&dir).is_ok() {
+        if !dir.pop() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn apply_sync(req: SyncApplyRequest) -> SyncApplyResponse {
+    let mut resp = SyncApplyResponse {
+        root: req.root.clone(),
+        ..Default::default()
+    };
+
+    let root = match resolve(&req.root, PathAccess::Write)
+        .and_then(|r| fs::create_dir_all(&r).map(|_| r).map_err(|e| e.to_string()))
+    {
+        Ok(r) => r,
+        Err(e) => {
+            resp.error = Some(e);
+            return resp;
+        }
+    };
+
+    resp.results = req
+        .files
+        .iter()
+        .map(|upload| match apply_upload(&root, upload) {
+            Ok(changed) => FileWriteResult {
+                path: upload.path.clone(),
+                success: true,
+                changed,
+                error: None,
+            },
+            Err(e) => FileWriteResult {
+                path: upload.path.clone(),
+                success: false,
+                changed: false,
+                error: Some(e),
+            },
+        })
+        .collect();
+
+    resp.deleted = req
+        .delete
+        .iter()
+        .map(|rel| match apply_delete(&root, rel) {
+            Ok(()) => SyncDeleteResult {
+                path: rel.clone(),
+                success: true,
+                error: None,
+            },
+            Err(e) => SyncDeleteResult {
+                path: rel.clone(),
+                success: false,
+                error: Some(e),
+            },
+        })
+        .collect();
+
+    resp
+}
+
+pub async fn handle_sync_plan(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let _permit = FILES_SEMAPHORE.acquire().await.unwrap();
+
+    let body = match read_body_limited(req, MAX_REQUEST_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(ReadBodyError::TooLarge) => {
+            return json_error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+        }
+        Err(ReadBodyError::ReadFailed) => {
+            return json_error(StatusCode::BAD_REQUEST, "Failed to read body")
+        }
+    };
+
+    let plan_req: SyncPlanRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &format!("Invalid JSON: {}", e)),
+    };
+
+    let plan = tokio::task::spawn_blocking(move || plan_sync(plan_req))
+        .await
+        .unwrap_or_default();
+
+    json_ok(serde_json::to_value(plan).unwrap())
+}
+
+pub async fn handle_sync_apply(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let _permit = FILES_SEMAPHORE.acquire().await.unwrap();
+
+    let body = match read_body_limited(req, MAX_REQUEST_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(ReadBodyError::TooLarge) => {
+            return json_error(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large")
+        }
+        Err(ReadBodyError::ReadFailed) => {
+            return json_error(StatusCode::BAD_REQUEST, "Failed to read body")
+        }
+    };
+
+    let apply_req: SyncApplyRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &format!("Invalid JSON: {}", e)),
+    };
+
+    let resp = tokio::task::spawn_blocking(move || apply_sync(apply_req))
+        .await
+        .unwrap_or_default();
+
+    let all_success = resp.error.is_none()
+        && resp.results.iter().all(|r| r.success)
+        && resp.deleted.iter().all(|d| d.success);
+    let status = if all_success {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+
+    json(
+        status,
+        Bytes::from(serde_json::to_vec(&resp).unwrap_or_default()),
+    )
+}
@@ -2,13 +2,18 @@ use http_body_util::Full;
 use hyper::body::Bytes;
 use hyper::{Request, Response, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::fs::{self, Permissions};
-use std::os::unix::fs::{chown, PermissionsExt};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
 use std::path::Path;
 
 use crate::body::{read_body_limited, ReadBodyError};
 use crate::limits::{FILES_SEMAPHORE, MAX_REQUEST_BODY_BYTES};
+use crate::path_policy::{resolve, PathAccess};
 use crate::response::{json, json_error, json_ok};
+use crate::rfc3339_from_secs;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -16,6 +21,20 @@ pub struct WriteFilesRequest {
     pub files: Vec<FileWrite>,
 }
 
+const DEFAULT_BLOCK_BEGIN: &str = "# >>> atelier managed block >>>";
+const DEFAULT_BLOCK_END: &str = "# <<< atelier managed block <<<";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WriteMode {
+    #[default]
+    Overwrite,
+    Append,
+    CreateOnly,
+    EnsureLine,
+    ManagedBlock,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileWrite {
@@ -25,6 +44,12 @@ pub struct FileWrite {
     pub mode: Option<String>,
     #[serde(default)]
     pub owner: Option<String>,
+    #[serde(default)]
+    pub write_mode: WriteMode,
+    #[serde(default)]
+    pub begin_marker: Option<String>,
+    #[serde(default)]
+    pub end_marker: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -38,6 +63,46 @@ pub struct WriteFilesResponse {
 pub struct FileWriteResult {
     pub path: String,
     pub success: bool,
+    pub changed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatFilesRequest {
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub checksum: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatFilesResponse {
+    pub results: Vec<FileStat>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileStat {
+    pub path: String,
+    pub exists: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
@@ -50,30 +115,186 @@ fn get_uid_gid(owner: &str) -> Option<(u32, u32)> {
     }
 }
 
+fn get_owner_name(uid: u32) -> Option<String> {
+    match uid {
+        1000 => Some("dev".to_string()),
+        0 => Some("root".to_string()),
+        _ => None,
+    }
+}
+
 fn parse_mode(mode_str: &str) -> Option<u32> {
     u32::from_str_radix(mode_str, 8).ok()
 }
 
-fn write_single_file(file: &FileWrite) -> Result<(), String> {
-    let path = Path::new(&file.path);
+pub fn read_existing(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+fn with_trailing_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{text}\n")
+    }
+}
+
+fn ensure_lines(existing: &str, content: &str) -> String {
+    let mut present: HashSet<&str> = existing.lines().map(|l| l.trim_end_matches('\r')).collect();
+    let mut out = with_trailing_newline(existing);
+    for line in content.lines() {
+        if present.insert(line) {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Replaces the block between `begin` and `end`, or appends one when the
+/// file has neither marker. Any other combination (a marker without its
+/// pair, several blocks, `end` before `begin`) is an error: guessing where
+/// the block ends could delete the user's content.
+fn replace_managed_block(
+    existing: &str,
+    content: &str,
+    begin: &str,
+    end: &str,
+) -> Result<String, String> {
+    let block = format!("{begin}\n{}{end}\n", with_trailing_newline(content));
+
+    let lines: Vec<&str> = existing.split_inclusive('\n').collect();
+    let marker_lines = |marker: &str| -> Vec<usize> {
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.trim_end() == marker)
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    match (marker_lines(begin).as_slice(), marker_lines(end).as_slice()) {
+        ([], []) => {
+            let mut out = with_trailing_newline(existing);
+            out.push_str(&block);
+            Ok(out)
+        }
+        (&[b], &[e]) if b < e => {
+            let mut out: String = lines[..b].concat();
+            out.push_str(&block);
+            out.push_str(&lines[e + 1..].concat());
+            Ok(out)
+        }
+        _ => Err("Managed block markers are missing or unbalanced".to_string()),
+    }
+}
+
+/// Computes the file's new content for `file.write_mode`, or `None` when the
+/// write would leave the file untouched.
+fn next_content(file: &FileWrite, path: &Path) -> Result<Option<Vec<u8>>, String> {
+    let existing = read_existing(path)?;
+
+    let next = match file.write_mode {
+        WriteMode::Overwrite | WriteMode::CreateOnly => file.content.clone().into_bytes(),
+        // Byte-level, so logs and latin-1 configs can be appended to.
+        WriteMode::Append => {
+            let mut bytes = existing.clone().unwrap_or_default();
+            bytes.extend_from_slice(file.content.as_bytes());
+            bytes
+        }
+        mode => {
+            let current = match &existing {
+                Some(bytes) => std::str::from_utf8(bytes)
+                    .map_err(|_| "Existing file is not valid UTF-8".to_string())?,
+                None => "",
+            };
+            match mode {
+                WriteMode::EnsureLine => ensure_lines(current, &file.content),
+                _ => replace_managed_block(
+                    current,
+                    &file.content,
+                    file.begin_marker.as_deref().unwrap_or(DEFAULT_BLOCK_BEGIN),
+                    file.end_marker.as_deref().unwrap_or(DEFAULT_BLOCK_END),
+                )?,
+            }
+            .into_bytes()
+        }
+    };
+
+    Ok(Some(next).filter(|n| existing.as_ref() != Some(n)))
+}
+
+/// Writes one file according to its write mode and returns whether the
+/// content on disk changed.
+pub fn write_single_file(file: &FileWrite) -> Result<bool, String> {
+    let resolved = resolve(&file.path, PathAccess::Write)?;
+    let path = resolved.as_path();
+
+    if file.write_mode == WriteMode::CreateOnly {
+        return create_new_file(path, file);
+    }
+
+    let next = next_content(file, path)?;
+    write_content(path, next, file.mode.as_deref(), file.owner.as_deref())
+}
 
+/// `CreateOnly`: creates the file exclusively, so a file appearing in the
+/// meantime or a dangling symlink is never written through. An existing
+/// file is left alone entirely, including its mode and owner.
+fn create_new_file(path: &Path, file: &FileWrite) -> Result<bool, String> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| format!("Failed to create parent dir: {}", e))?;
     }
+    let mut out = match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(false),
+        Err(e) => return Err(format!("Failed to write file: {}", e)),
+    };
+    out.write_all(file.content.as_bytes())
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    apply_mode_owner(path, file.mode.as_deref(), file.owner.as_deref())?;
+    Ok(true)
+}
 
-    fs::write(path, &file.content).map_err(|e| format!("Failed to write file: {}", e))?;
+/// Writes `next` to an already resolved `path` unless it's `None` (content
+/// unchanged), then applies mode and owner. Returns whether the content on
+/// disk changed.
+pub fn write_content(
+    path: &Path,
+    next: Option<Vec<u8>>,
+    mode: Option<&str>,
+    owner: Option<&str>,
+) -> Result<bool, String> {
+    let changed = next.is_some();
 
-    if let Some(mode_str) = &file.mode {
-        if let Some(mode) = parse_mode(mode_str) {
-            fs::set_permissions(path, Permissions::from_mode(mode))
-                .map_err(|e| format!("Failed to set mode: {}", e))?;
+    if let Some(content) = next {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create parent dir: {}", e))?;
         }
+        fs::write(path, content).map_err(|e| format!("Failed to write file: {}", e))?;
     }
 
-    if let Some(owner) = &file.owner {
-        if let Some((uid, gid)) = get_uid_gid(owner) {
-            chown(path, Some(uid), Some(gid)).map_err(|e| format!("Failed to chown: {}", e))?;
-        }
+    apply_mode_owner(path, mode, owner)?;
+    Ok(changed)
+}
+
+fn apply_mode_owner(path: &Path, mode: Option<&str>, owner: Option<&str>) -> Result<(), String> {
+    if let Some(mode_str) = mode
+        && let Some(mode) = parse_mode(mode_str)
+    {
+        fs::set_permissions(path, Permissions::from_mode(mode))
+            .map_err(|e| format!("Failed to set mode: {}", e))?;
+    }
+
+    if let Some(owner) = owner
+        && let Some((uid, gid)) = get_uid_gid(owner)
+    {
+        chown(path, Some(uid), Some(gid)).map_err(|e| format!("Failed to chown: {}", e))?;
     }
 
     Ok(())
@@ -104,14 +325,16 @@ pub async fn handle_write_files(req: Request<hyper::body::Incoming>) -> Response
         .files
         .iter()
         .map(|file| match write_single_file(file) {
-            Ok(()) => FileWriteResult {
+            Ok(changed) => FileWriteResult {
                 path: file.path.clone(),
                 success: true,
+                changed,
                 error: None,
             },
             Err(e) => FileWriteResult {
                 path: file.path.clone(),
                 success: false,
+                changed: false,
                 error: Some(e),
             },
         })
@@ -129,3 +352,102 @@ pub async fn handle_write_files(req: Request<hyper::body::Incoming>) -> Response
         Bytes::from(serde_json::to_vec(&WriteFilesResponse { results }).unwrap_or_default()),
     )
 }
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256_bytes(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn stat_single_path(path: &str, checksum: bool) -> FileStat {
+    let mut stat = FileStat {
+        path: path.to_string(),
+        ..Default::default()
+    };
+
+    let resolved = match resolve(path, PathAccess::Read) {
+        Ok(p) => p,
+        Err(e) => {
+            stat.error = Some(e);
+            return stat;
+        }
+    };
+
+    let meta = match fs::metadata(&resolved) {
+        Ok(m) => m,
+        Err(e) if e.kind() == ErrorKind::NotFound => return stat,
+        Err(e) => {
+            stat.error = Some(format!("Failed to stat: {}", e));
+            return stat;
+        }
+    };
+
+    stat.exists = true;
+    stat.kind = Some(if meta.is_file() {
+        "file"
+    } else if meta.is_dir() {
+        "dir"
+    } else {
+        "other"
+    });
+    stat.size = Some(meta.len());
+    stat.mode = Some(format!("{:o}", meta.mode() & 0o7777));
+    stat.owner = get_owner_name(meta.uid());
+    stat.uid = Some(meta.uid());
+    stat.gid = Some(meta.gid());
+    stat.mtime = Some(rfc3339_from_secs(meta.mtime().max(0) as u64));
+
+    if checksum && meta.is_file() {
+        match sha256_file(&resolved) {
+            Ok(hash) => stat.sha256 = Some(hash),
+            Err(e) => stat.error = Some(e),
+        }
+    }
+
+    stat
+}
+
+pub async fn handle_stat_files(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let _permit = FILES_SEMAPHORE.acquire().await.unwrap();
+
+    let body = match read_body_limited(req, MAX_REQUEST_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(ReadBodyError::TooLarge) => {
+            return json_ok(serde_json::json!({
+                "results": [],
+                "error": "Request body too large"
+            }))
+        }
+        Err(ReadBodyError::ReadFailed) => {
+            return json_error(StatusCode::BAD_REQUEST, "Failed to read body")
+        }
+    };
+
+    let stat_req: StatFilesRequest = match serde_json::from_slice(&body) {
+        Ok(r) => r,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &format!("Invalid JSON: {}", e)),
+    };
+
+    // Hashing large files is blocking I/O; keep it off the runtime threads so
+    // the watchdog heartbeat keeps ticking.
+    let results = tokio::task::spawn_blocking(move || {
+        stat_req
+            .paths
+            .iter()
+            .map(|p| stat_single_path(p, stat_req.checksum))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    json_ok(serde_json::to_value(StatFilesResponse { results }).unwrap())
+}
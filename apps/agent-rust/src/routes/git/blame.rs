@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{full_path, handle_json_request, run_as_dev, shell_quote};
+use crate::rfc3339_from_secs;
+
+// Hash git blame gives lines that aren't committed yet.
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlameBody {
+    repo_path: String,
+    path: String,
+    /// Blame as of this commit; the working tree by default.
+    #[serde(default, rename = "ref")]
+    rev: Option<String>,
+    /// 1-based, inclusive.
+    #[serde(default)]
+    start_line: Option<u32>,
+    #[serde(default)]
+    end_line: Option<u32>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitBlameCommit {
+    short_hash: String,
+    author: String,
+    author_email: String,
+    author_time: u64,
+    date: String,
+    summary: String,
+}
+
+/// `count` consecutive lines from `start` last changed by `commit`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitBlameRange {
+    start: u32,
+    count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    working_tree: bool,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitBlameResponse {
+    path: String,
+    file: String,
+    ranges: Vec<GitBlameRange>,
+    /// Details for every commit `ranges` refers to, by full hash.
+    commits: BTreeMap<String, GitBlameCommit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitBlameResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Folds `--porcelain` output into ranges. Each line starts with a
+/// "<hash> <orig> <final> [<count>]" header; the first header for a commit
+/// is followed by its details, and every line ends with "\t<content>".
+fn parse_porcelain(out: &str) -> (Vec<GitBlameRange>, BTreeMap<String, GitBlameCommit>) {
+    let mut ranges: Vec<GitBlameRange> = Vec::new();
+    let mut commits: BTreeMap<String, GitBlameCommit> = BTreeMap::new();
+    let mut current: Option<String> = None;
+
+    for line in out.lines() {
+        if line.starts_with('\t') {
+            current = None;
+            continue;
+        }
+        if current.is_none() {
+            let mut fields = line.split(' ');
+            let (Some(hash), Some(_), Some(final_line)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Ok(final_line) = final_line.parse::<u32>() else {
+                continue;
+            };
+            let working_tree = hash == UNCOMMITTED;
+            let commit = (!working_tree).then(|| hash.to_string());
+            match ranges.last_mut() {
+                Some(last) if last.commit == commit && last.start + last.count == final_line => {
+                    last.count += 1;
+                }
+                _ => ranges.push(GitBlameRange {
+                    start: final_line,
+                    count: 1,
+                    commit: commit.clone(),
+                    working_tree,
+                }),
+            }
+            if let Some(hash) = commit {
+                commits
+                    .entry(hash.clone())
+                    .or_insert_with(|| GitBlameCommit {
+                        short_hash: hash[..hash.len().min(7)].to_string(),
+                        ..Default::default()
+                    });
+            }
+            current = Some(hash.to_string());
+            continue;
+        }
+
+        let Some(entry) = current.as_deref().and_then(|h| commits.get_mut(h)) else {
+            continue;
+        };
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" => entry.author = value.to_string(),
+            "author-mail" => {
+                entry.author_email = value.trim_matches(['<', '>']).to_string();
+            }
+            "author-time" => {
+                entry.author_time = value.parse().unwrap_or(0);
+                entry.date = rfc3339_from_secs(entry.author_time);
+            }
+            "summary" => entry.summary = value.to_string(),
+            _ => {}
+        }
+    }
+    (ranges, commits)
+}
+
+async fn blame(body: BlameBody) -> GitBlameResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitBlameResponse {
+        path: body.repo_path,
+        file: body.path.clone(),
+        ..Default::default()
+    };
+
+    if body.path.is_empty() {
+        resp.error = Some("path is required".to_string());
+        return resp;
+    }
+    let rev = match body.rev.as_deref().filter(|r| !r.is_empty()) {
+        Some(rev) if rev.starts_with('-') => {
+            resp.error = Some(format!("Invalid ref: {rev}"));
+            return resp;
+        }
+        Some(rev) => format!(" {}", shell_quote(rev)),
+        None => String::new(),
+    };
+    let range = match (body.start_line, body.end_line) {
+        (None, None) => String::new(),
+        (start, end) => {
+            let start = start.unwrap_or(1).max(1);
+            let end = end.map_or(String::new(), |e| e.max(start).to_string());
+            format!(" -L {start},{end}")
+        }
+    };
+
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' blame --porcelain{range}{rev} -- {}",
+        shell_quote(&body.path)
+    ))
+    .await;
+    if code != 0 {
+        resp.error = Some(stderr.trim().to_string());
+        return resp;
+    }
+
+    (resp.ranges, resp.commits) = parse_porcelain(&out);
+    resp
+}
+
+pub async fn handle_git_blame(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitBlameResponse::failed, blame).await
+}
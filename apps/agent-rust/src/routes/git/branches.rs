@@ -0,0 +1,423 @@
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    default_true, full_path, handle_json_request, read_json_body, run_as_dev, shell_quote,
+};
+use crate::limits::GIT_SEMAPHORE;
+use crate::response::json_ok;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListBranchesBody {
+    repo_path: String,
+    #[serde(default = "default_true")]
+    remote: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateBranchBody {
+    repo_path: String,
+    name: String,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    checkout: bool,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum DirtyPolicy {
+    /// Refuse to switch while tracked files have changes.
+    #[default]
+    Fail,
+    /// Stash everything (untracked included) and leave it stashed.
+    Stash,
+    /// Let git carry the changes over; fails if they conflict.
+    Carry,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckoutBranchBody {
+    repo_path: String,
+    name: String,
+    #[serde(default)]
+    dirty: DirtyPolicy,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RenameBranchBody {
+    repo_path: String,
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteBranchBody {
+    repo_path: String,
+    name: String,
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitBranch {
+    name: String,
+    remote: bool,
+    current: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upstream: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    upstream_gone: bool,
+    ahead: u32,
+    behind: u32,
+    last_commit: String,
+    last_commit_date: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitBranchesResponse {
+    path: String,
+    current: Option<String>,
+    branches: Vec<GitBranch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitBranchActionResponse {
+    path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merged: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitBranchActionResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parses `%(upstream:track,nobracket)`: "ahead 1, behind 2", "gone" or "".
+fn parse_track(track: &str) -> (u32, u32, bool) {
+    let mut ahead = 0;
+    let mut behind = 0;
+    for part in track.split(", ") {
+        if let Some(n) = part.strip_prefix("ahead ") {
+            ahead = n.parse().unwrap_or(0);
+        } else if let Some(n) = part.strip_prefix("behind ") {
+            behind = n.parse().unwrap_or(0);
+        }
+    }
+    (ahead, behind, track == "gone")
+}
+
+async fn validate_branch_name(path: &str, name: &str) -> Result<(), String> {
+    let (code, _, _) = run_as_dev(&format!(
+        "git -C '{path}' check-ref-format --branch {}",
+        shell_quote(name)
+    ))
+    .await;
+    if code != 0 || name.starts_with('-') {
+        return Err(format!("Invalid branch name: {name}"));
+    }
+    Ok(())
+}
+
+async fn list_branches(body: ListBranchesBody) -> GitBranchesResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitBranchesResponse {
+        path: body.repo_path,
+        current: None,
+        branches: Vec::new(),
+        error: None,
+    };
+
+    let refs = if body.remote {
+        "refs/heads refs/remotes"
+    } else {
+        "refs/heads"
+    };
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' for-each-ref --sort=-committerdate --format='%(refname)%00%(refname:short)%00%(HEAD)%00%(upstream:short)%00%(upstream:track,nobracket)%00%(objectname:short) %(subject)%00%(committerdate:iso-strict)' {refs}"
+    ))
+    .await;
+    if code != 0 {
+        resp.error = Some(stderr.trim().to_string());
+        return resp;
+    }
+
+    for line in out.lines() {
+        let fields: Vec<&str> = line.split('\0').collect();
+        let [refname, name, head, upstream, track, last_commit, date] = fields.as_slice() else {
+            continue;
+        };
+        // refs/remotes/origin/HEAD is an alias, not a branch.
+        if refname.starts_with("refs/remotes/") && refname.ends_with("/HEAD") {
+            continue;
+        }
+        let (ahead, behind, upstream_gone) = parse_track(track);
+        let current = *head == "*";
+        if current {
+            resp.current = Some(name.to_string());
+        }
+        resp.branches.push(GitBranch {
+            name: name.to_string(),
+            remote: refname.starts_with("refs/remotes/"),
+            current,
+            upstream: (!upstream.is_empty()).then(|| upstream.to_string()),
+            upstream_gone,
+            ahead,
+            behind,
+            last_commit: last_commit.to_string(),
+            last_commit_date: date.to_string(),
+        });
+    }
+    resp
+}
+
+async fn create_branch(body: CreateBranchBody) -> GitBranchActionResponse {
+    let path = full_path(&body.repo_path);
+    if let Err(e) = validate_branch_name(&path, &body.name).await {
+        return GitBranchActionResponse::failed(body.repo_path, e);
+    }
+    if let Some(from) = body.from.as_deref().filter(|f| f.starts_with('-')) {
+        return GitBranchActionResponse::failed(
+            body.repo_path,
+            format!("Invalid start point: {from}"),
+        );
+    }
+
+    let name = shell_quote(&body.name);
+    let from = body
+        .from
+        .as_deref()
+        .map(|f| format!(" {}", shell_quote(f)))
+        .unwrap_or_default();
+    let cmd = if body.checkout {
+        format!("git -C '{path}' switch -c {name}{from}")
+    } else {
+        format!("git -C '{path}' branch {name}{from}")
+    };
+    let (code, _, stderr) = run_as_dev(&cmd).await;
+    if code != 0 {
+        return GitBranchActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    GitBranchActionResponse {
+        path: body.repo_path,
+        success: true,
+        branch: Some(body.name),
+        ..Default::default()
+    }
+}
+
+async fn checkout_branch(body: CheckoutBranchBody) -> GitBranchActionResponse {
+    let path = full_path(&body.repo_path);
+    if let Err(e) = validate_branch_name(&path, &body.name).await {
+        return GitBranchActionResponse::failed(body.repo_path, e);
+    }
+    let name = shell_quote(&body.name);
+    let mut stash = None;
+
+    match body.dirty {
+        DirtyPolicy::Fail => {
+            // Untracked files survive a switch (git refuses if one would be
+            // overwritten), so only tracked changes count as dirty here.
+            let (_, out, _) = run_as_dev(&format!(
+                "git -C '{path}' status --porcelain --untracked-files=no | head -1"
+            ))
+            .await;
+            if !out.trim().is_empty() {
+                return GitBranchActionResponse::failed(
+                    body.repo_path,
+                    "Working tree has uncommitted changes",
+                );
+            }
+        }
+        DirtyPolicy::Stash => {
+            let (_, out, _) =
+                run_as_dev(&format!("git -C '{path}' status --porcelain | head -1")).await;
+            if !out.trim().is_empty() {
+                let message = shell_quote(&format!("atelier: before checkout of {}", body.name));
+                let (code, _, stderr) = run_as_dev(&format!(
+                    "git -C '{path}' stash push --include-untracked -m {message}"
+                ))
+                .await;
+                if code != 0 {
+                    return GitBranchActionResponse::failed(body.repo_path, stderr.trim());
+                }
+                let (_, rev, _) =
+                    run_as_dev(&format!("git -C '{path}' rev-parse --short stash@{{0}}")).await;
+                stash = Some(rev.trim().to_string());
+            }
+        }
+        DirtyPolicy::Carry => {}
+    }
+
+    // `switch` also creates a tracking branch when only `origin/<name>` exists.
+    let (code, _, stderr) = run_as_dev(&format!("git -C '{path}' switch {name}")).await;
+    if code != 0 {
+        let mut error = stderr.trim().to_string();
+        if let Some(rev) = &stash {
+            error.push_str(&format!("\nChanges were stashed as {rev}"));
+        }
+        return GitBranchActionResponse {
+            stash,
+            ..GitBranchActionResponse::failed(body.repo_path, error)
+        };
+    }
+
+    GitBranchActionResponse {
+        path: body.repo_path,
+        success: true,
+        branch: Some(body.name),
+        stash,
+        ..Default::default()
+    }
+}
+
+async fn rename_branch(body: RenameBranchBody) -> GitBranchActionResponse {
+    let path = full_path(&body.repo_path);
+    if let Err(e) = validate_branch_name(&path, &body.to).await {
+        return GitBranchActionResponse::failed(body.repo_path, e);
+    }
+    if body.from.starts_with('-') {
+        return GitBranchActionResponse::failed(
+            body.repo_path,
+            format!("Invalid branch name: {}", body.from),
+        );
+    }
+
+    let (code, _, stderr) = run_as_dev(&format!(
+        "git -C '{path}' branch -m {} {}",
+        shell_quote(&body.from),
+        shell_quote(&body.to)
+    ))
+    .await;
+    if code != 0 {
+        return GitBranchActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    GitBranchActionResponse {
+        path: body.repo_path,
+        success: true,
+        branch: Some(body.to),
+        ..Default::default()
+    }
+}
+
+/// A branch counts as merged when HEAD or its own upstream contains it, the
+/// same rule `git branch -d` applies.
+async fn is_merged(path: &str, name: &str) -> bool {
+    let branch = shell_quote(&format!("refs/heads/{name}"));
+    let (code, _, _) = run_as_dev(&format!(
+        "git -C '{path}' merge-base --is-ancestor {branch} HEAD"
+    ))
+    .await;
+    if code == 0 {
+        return true;
+    }
+    let upstream = shell_quote(&format!("{name}@{{upstream}}"));
+    let (code, _, _) = run_as_dev(&format!(
+        "git -C '{path}' merge-base --is-ancestor {branch} {upstream}"
+    ))
+    .await;
+    code == 0
+}
+
+async fn delete_branch(body: DeleteBranchBody) -> GitBranchActionResponse {
+    let path = full_path(&body.repo_path);
+
+    let (code, _, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --verify --quiet {}",
+        shell_quote(&format!("refs/heads/{}", body.name))
+    ))
+    .await;
+    if code != 0 {
+        return GitBranchActionResponse::failed(
+            body.repo_path,
+            format!("Branch not found: {}", body.name),
+        );
+    }
+
+    let merged = is_merged(&path, &body.name).await;
+    if !merged && !body.force {
+        return GitBranchActionResponse {
+            merged: Some(false),
+            ..GitBranchActionResponse::failed(
+                body.repo_path,
+                format!("Branch '{}' is not fully merged", body.name),
+            )
+        };
+    }
+
+    let (code, _, stderr) = run_as_dev(&format!(
+        "git -C '{path}' branch -D {}",
+        shell_quote(&body.name)
+    ))
+    .await;
+    if code != 0 {
+        return GitBranchActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    GitBranchActionResponse {
+        path: body.repo_path,
+        success: true,
+        branch: Some(body.name),
+        merged: Some(merged),
+        ..Default::default()
+    }
+}
+
+pub async fn handle_git_branches(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: ListBranchesBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => return json_ok(serde_json::json!({"path": "", "branches": [], "error": e})),
+    };
+    let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+    json_ok(serde_json::to_value(list_branches(body).await).unwrap())
+}
+
+pub async fn handle_git_branch_create(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitBranchActionResponse::failed, create_branch).await
+}
+
+pub async fn handle_git_branch_checkout(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitBranchActionResponse::failed, checkout_branch).await
+}
+
+pub async fn handle_git_branch_rename(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitBranchActionResponse::failed, rename_branch).await
+}
+
+pub async fn handle_git_branch_delete(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitBranchActionResponse::failed, delete_branch).await
+}
@@ -0,0 +1,439 @@
+// Working tree checkpoints. A checkpoint is a commit of the whole working
+// tree (tracked and untracked, minus ignored files) on a hidden ref. It is
+// built through a throwaway index, so the real index, HEAD and the branch
+// are never touched, and nothing shows up in `git log` or `git stash list`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{GitDiffFile, full_path, handle_json_request, run_as_dev, shell_quote};
+
+const CHECKPOINT_REFS: &str = "refs/atelier/checkpoints";
+// Automatic pruning after each new checkpoint.
+const MAX_CHECKPOINTS: usize = 50;
+const MAX_CHECKPOINT_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+// Checkpoint commits get a fixed identity so they work before dev has
+// configured git.
+const CHECKPOINT_IDENTITY: &str = "GIT_AUTHOR_NAME=atelier GIT_AUTHOR_EMAIL=atelier@localhost \
+     GIT_COMMITTER_NAME=atelier GIT_COMMITTER_EMAIL=atelier@localhost";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateBody {
+    repo_path: String,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListBody {
+    repo_path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckpointBody {
+    repo_path: String,
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PruneBody {
+    repo_path: String,
+    #[serde(default)]
+    max_count: Option<usize>,
+    #[serde(default)]
+    max_age_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitCheckpoint {
+    id: String,
+    #[serde(rename = "ref")]
+    name: String,
+    hash: String,
+    /// HEAD when the checkpoint was taken.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    head: Option<String>,
+    message: String,
+    created_at: String,
+    #[serde(skip)]
+    created_secs: u64,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitCheckpointListResponse {
+    path: String,
+    checkpoints: Vec<GitCheckpoint>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitCheckpointDiffResponse {
+    path: String,
+    files: Vec<GitDiffFile>,
+    total_added: u32,
+    total_removed: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitCheckpointActionResponse {
+    path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkpoint: Option<GitCheckpoint>,
+    /// Checkpoint of the state a restore replaced, so it can be undone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<GitCheckpoint>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pruned: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitCheckpointActionResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+fn generate_checkpoint_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("ckpt_{:x}", nanos)
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Runs `cmd` with `GIT_INDEX_FILE` pointing at a scratch copy of the
+/// repo's index, removed afterwards. Copying keeps git's stat cache, so
+/// only changed files get re-hashed.
+async fn with_scratch_index(path: &str, cmd: &str) -> (i32, String, String) {
+    let (code, index, stderr) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --path-format=absolute --git-path index"
+    ))
+    .await;
+    if code != 0 {
+        return (code, String::new(), stderr);
+    }
+    let index = index.trim();
+    let scratch = shell_quote(&format!("{index}.{}", generate_checkpoint_id()));
+    run_as_dev(&format!(
+        "export GIT_INDEX_FILE={scratch}; trap 'rm -f \"$GIT_INDEX_FILE\"' EXIT; \
+         {{ cp {} \"$GIT_INDEX_FILE\" 2>/dev/null || true; }} && {cmd}",
+        shell_quote(index)
+    ))
+    .await
+}
+
+/// Tree of the working tree as it is now.
+async fn snapshot_tree(path: &str) -> Result<String, String> {
+    let (code, out, stderr) = with_scratch_index(
+        path,
+        &format!("git -C '{path}' add -A && git -C '{path}' write-tree"),
+    )
+    .await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(out.trim().to_string())
+}
+
+async fn checkpoints(path: &str) -> Result<Vec<GitCheckpoint>, String> {
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' for-each-ref --sort=-refname --sort=-creatordate \
+         --format='%(refname)%00%(objectname)%00%(parent)%00%(creatordate:iso-strict)%00%(creatordate:unix)%00%(contents:subject)' \
+         {CHECKPOINT_REFS}"
+    ))
+    .await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(out
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\0').collect();
+            let [name, hash, parent, date, secs, subject] = fields.as_slice() else {
+                return None;
+            };
+            Some(GitCheckpoint {
+                id: name.rsplit('/').next()?.to_string(),
+                name: name.to_string(),
+                hash: hash.to_string(),
+                head: (!parent.is_empty()).then(|| parent.to_string()),
+                message: subject.to_string(),
+                created_at: date.to_string(),
+                created_secs: secs.parse().unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+async fn find_checkpoint(path: &str, id: &str) -> Result<GitCheckpoint, String> {
+    if !valid_id(id) {
+        return Err(format!("Invalid checkpoint id: {id}"));
+    }
+    checkpoints(path)
+        .await?
+        .into_iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("No checkpoint {id}"))
+}
+
+async fn create_checkpoint(path: &str, message: &str) -> Result<GitCheckpoint, String> {
+    let tree = snapshot_tree(path).await?;
+    let (has_head, head, _) =
+        run_as_dev(&format!("git -C '{path}' rev-parse --verify --quiet HEAD")).await;
+    let parent = if has_head == 0 {
+        format!(" -p {}", head.trim())
+    } else {
+        String::new()
+    };
+
+    let id = generate_checkpoint_id();
+    let name = format!("{CHECKPOINT_REFS}/{id}");
+    let (code, _, stderr) = run_as_dev(&format!(
+        "commit=$({CHECKPOINT_IDENTITY} git -C '{path}' commit-tree {tree}{parent} -m {}) && \
+         git -C '{path}' update-ref '{name}' \"$commit\"",
+        shell_quote(message)
+    ))
+    .await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    find_checkpoint(path, &id).await
+}
+
+/// Deletes checkpoints beyond `max_count` (newest kept) or older than
+/// `max_age_secs`. Returns the ids removed.
+async fn prune_checkpoints(path: &str, max_count: usize, max_age_secs: u64) -> Vec<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let stale: Vec<GitCheckpoint> = checkpoints(path)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .filter(|(i, c)| *i >= max_count || now.saturating_sub(c.created_secs) > max_age_secs)
+        .map(|(_, c)| c)
+        .collect();
+    if stale.is_empty() {
+        return Vec::new();
+    }
+    let deletes: String = stale
+        .iter()
+        .map(|c| format!("delete {}\n", c.name))
+        .collect();
+    let (code, _, _) = run_as_dev(&format!(
+        "printf %s {} | git -C '{path}' update-ref --stdin",
+        shell_quote(&deletes)
+    ))
+    .await;
+    if code != 0 {
+        return Vec::new();
+    }
+    stale.into_iter().map(|c| c.id).collect()
+}
+
+async fn create(body: CreateBody) -> GitCheckpointActionResponse {
+    let path = full_path(&body.repo_path);
+    let message = body
+        .message
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| "Checkpoint".to_string());
+    let checkpoint = match create_checkpoint(&path, &message).await {
+        Ok(c) => c,
+        Err(e) => return GitCheckpointActionResponse::failed(body.repo_path, e),
+    };
+    GitCheckpointActionResponse {
+        path: body.repo_path,
+        success: true,
+        pruned: prune_checkpoints(&path, MAX_CHECKPOINTS, MAX_CHECKPOINT_AGE_SECS).await,
+        checkpoint: Some(checkpoint),
+        ..Default::default()
+    }
+}
+
+async fn list(body: ListBody) -> GitCheckpointListResponse {
+    let path = full_path(&body.repo_path);
+    match checkpoints(&path).await {
+        Ok(checkpoints) => GitCheckpointListResponse {
+            path: body.repo_path,
+            checkpoints,
+            error: None,
+        },
+        Err(e) => GitCheckpointListResponse {
+            path: body.repo_path,
+            error: Some(e),
+            ..Default::default()
+        },
+    }
+}
+
+/// What changed from the checkpoint to the current working tree.
+async fn diff(body: CheckpointBody) -> GitCheckpointDiffResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitCheckpointDiffResponse {
+        path: body.repo_path,
+        ..Default::default()
+    };
+    let result = async {
+        let checkpoint = find_checkpoint(&path, &body.id).await?;
+        let current = snapshot_tree(&path).await?;
+        let (code, out, stderr) = run_as_dev(&format!(
+            "git -C '{path}' diff-tree -r --numstat -z --no-renames {} {current}",
+            checkpoint.hash
+        ))
+        .await;
+        if code != 0 {
+            return Err(stderr.trim().to_string());
+        }
+        Ok(out)
+    }
+    .await;
+
+    match result {
+        Ok(out) => {
+            resp.files = out
+                .split('\0')
+                .filter_map(|entry| {
+                    let mut parts = entry.splitn(3, '\t');
+                    let (added, removed, file) = (parts.next()?, parts.next()?, parts.next()?);
+                    Some(GitDiffFile {
+                        path: file.to_string(),
+                        added: added.parse().unwrap_or(0),
+                        removed: removed.parse().unwrap_or(0),
+                        ..Default::default()
+                    })
+                })
+                .collect();
+            resp.total_added = resp.files.iter().map(|f| f.added).sum();
+            resp.total_removed = resp.files.iter().map(|f| f.removed).sum();
+        }
+        Err(e) => resp.error = Some(e),
+    }
+    resp
+}
+
+/// Puts the working tree back to the checkpoint: files are rewritten, and
+/// files created since are removed. The index and HEAD stay as they are.
+async fn restore(body: CheckpointBody) -> GitCheckpointActionResponse {
+    let path = full_path(&body.repo_path);
+    let checkpoint = match find_checkpoint(&path, &body.id).await {
+        Ok(c) => c,
+        Err(e) => return GitCheckpointActionResponse::failed(body.repo_path, e),
+    };
+    let backup = match create_checkpoint(&path, &format!("Before restoring {}", body.id)).await {
+        Ok(c) => c,
+        Err(e) => return GitCheckpointActionResponse::failed(body.repo_path, e),
+    };
+
+    // Files the backup has and the checkpoint doesn't were created since.
+    // Their names go to `rm` on stdin: a large change set would overflow
+    // the argument list.
+    let (code, _, stderr) = with_scratch_index(
+        &path,
+        &format!(
+            "set -o pipefail && git -C '{path}' read-tree {checkpoint} && \
+             git -C '{path}' checkout-index -a -f && \
+             git -C '{path}' diff-tree -r --name-only -z --no-renames --diff-filter=A \
+             {checkpoint} {backup} | {{ cd '{path}' && xargs -0 -r rm -f --; }}",
+            checkpoint = checkpoint.hash,
+            backup = backup.hash,
+        ),
+    )
+    .await;
+    if code != 0 {
+        return GitCheckpointActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    GitCheckpointActionResponse {
+        path: body.repo_path,
+        success: true,
+        checkpoint: Some(checkpoint),
+        backup: Some(backup),
+        ..Default::default()
+    }
+}
+
+async fn prune(body: PruneBody) -> GitCheckpointActionResponse {
+    let path = full_path(&body.repo_path);
+    GitCheckpointActionResponse {
+        success: true,
+        pruned: prune_checkpoints(
+            &path,
+            body.max_count.unwrap_or(MAX_CHECKPOINTS),
+            body.max_age_secs.unwrap_or(MAX_CHECKPOINT_AGE_SECS),
+        )
+        .await,
+        path: body.repo_path,
+        ..Default::default()
+    }
+}
+
+pub async fn handle_git_checkpoint(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitCheckpointActionResponse::failed, create).await
+}
+
+pub async fn handle_git_checkpoint_list(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(
+        req,
+        |_, e| GitCheckpointListResponse {
+            error: Some(e),
+            ..Default::default()
+        },
+        list,
+    )
+    .await
+}
+
+pub async fn handle_git_checkpoint_diff(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(
+        req,
+        |_, e| GitCheckpointDiffResponse {
+            error: Some(e),
+            ..Default::default()
+        },
+        diff,
+    )
+    .await
+}
+
+pub async fn handle_git_checkpoint_restore(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitCheckpointActionResponse::failed, restore).await
+}
+
+pub async fn handle_git_checkpoint_prune(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitCheckpointActionResponse::failed, prune).await
+}
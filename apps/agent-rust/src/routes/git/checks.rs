@@ -0,0 +1,262 @@
+// Pre-commit checks: commands from the repo's config plus whatever hook
+// tooling the repo itself sets up (the pre-commit framework, husky,
+// lint-staged). Like the hooks they mirror, the detected tools only look at
+// staged files unless `allFiles` is set.
+
+use std::path::Path;
+use std::time::Instant;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{full_path, parse_exec_value, read_json_body};
+use crate::command::run_shell_command_limited;
+use crate::config::get_config;
+use crate::limits::{EXEC_SEMAPHORE, MAX_COMMAND_OUTPUT_BYTES};
+use crate::response::json_ok;
+
+// Per check; linters and type checkers on a big repo are slow.
+pub(super) const DEFAULT_CHECK_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+const MAX_CHECK_TIMEOUT_MS: u64 = 30 * 60 * 1000;
+// Only the end of a check's output is returned; that's where the failures are.
+const MAX_CHECK_OUTPUT_CHARS: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChecksBody {
+    repo_path: String,
+    /// Run only the checks with these names.
+    #[serde(default)]
+    only: Vec<String>,
+    #[serde(default)]
+    all_files: bool,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum CheckSource {
+    Config,
+    PreCommit,
+    Husky,
+    LintStaged,
+}
+
+struct Check {
+    name: String,
+    source: CheckSource,
+    command: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GitCheckResult {
+    pub(super) name: String,
+    source: CheckSource,
+    command: String,
+    pub(super) passed: bool,
+    exit_code: i32,
+    duration_ms: u64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    timed_out: bool,
+    output: String,
+}
+
+impl GitCheckResult {
+    /// Whether `hook`, the pre-commit hook git would run, does nothing but
+    /// this check: the script the pre-commit framework installs, or husky's
+    /// (`.husky/_` since v9, `.husky` before).
+    pub(super) fn runs_hook(&self, repo: &Path, hook: &Path) -> bool {
+        match self.source {
+            CheckSource::PreCommit => std::fs::read_to_string(hook)
+                .is_ok_and(|s| s.contains("# File generated by pre-commit")),
+            CheckSource::Husky => hook
+                .parent()
+                .is_some_and(|dir| dir == repo.join(".husky") || dir == repo.join(".husky/_")),
+            CheckSource::Config | CheckSource::LintStaged => false,
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitChecksResponse {
+    path: String,
+    passed: bool,
+    checks: Vec<GitCheckResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// lint-staged reads its config from package.json or one of its rc files.
+fn has_lint_staged_config(repo: &Path) -> bool {
+    let rc = std::fs::read_dir(repo).is_ok_and(|entries| {
+        entries.flatten().any(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with(".lintstagedrc") || name.starts_with("lint-staged.config.")
+        })
+    });
+    rc || std::fs::read_to_string(repo.join("package.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .is_some_and(|pkg| pkg.get("lint-staged").is_some())
+}
+
+fn detect_checks(path: &str, repo_path: &str, all_files: bool) -> Vec<Check> {
+    let repo = Path::new(path);
+    let mut checks: Vec<Check> = get_config()
+        .and_then(|c| {
+            c.repos
+                .into_iter()
+                .find(|r| r.clone_path.trim_end_matches('/') == repo_path.trim_end_matches('/'))
+        })
+        .map(|r| r.checks)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| Check {
+            name: c.name,
+            source: CheckSource::Config,
+            command: c.command,
+        })
+        .collect();
+
+    if repo.join(".pre-commit-config.yaml").is_file() {
+        let scope = if all_files { " --all-files" } else { "" };
+        checks.push(Check {
+            name: "pre-commit".to_string(),
+            source: CheckSource::PreCommit,
+            command: format!("pre-commit run --show-diff-on-failure --color=never{scope}"),
+        });
+    }
+
+    // A husky pre-commit hook usually just calls lint-staged; running both
+    // would check everything twice. The hook only sees staged files, so
+    // `allFiles` goes straight to lint-staged.
+    if repo.join(".husky/pre-commit").is_file() && !all_files {
+        checks.push(Check {
+            name: "husky".to_string(),
+            source: CheckSource::Husky,
+            command: "sh .husky/pre-commit".to_string(),
+        });
+    } else if has_lint_staged_config(repo) {
+        // lint-staged has no "all files" mode; `--diff` against the empty
+        // tree makes every tracked file count as changed.
+        let scope = if all_files {
+            " --diff=4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+        } else {
+            ""
+        };
+        checks.push(Check {
+            name: "lint-staged".to_string(),
+            source: CheckSource::LintStaged,
+            command: format!("npx --no-install lint-staged{scope}"),
+        });
+    }
+    checks
+}
+
+fn output_tail(stdout: &str, stderr: &str) -> String {
+    let output = format!("{stdout}{stderr}");
+    let output = output.trim_end();
+    match output.char_indices().rev().nth(MAX_CHECK_OUTPUT_CHARS) {
+        Some((cut, _)) => format!("…{}", &output[cut..]),
+        None => output.to_string(),
+    }
+}
+
+async fn run_check(path: &str, check: Check, timeout_ms: u64) -> GitCheckResult {
+    // Checks are arbitrary commands that can run for minutes, so they count
+    // against exec's limit rather than holding a git slot.
+    let _permit = EXEC_SEMAPHORE.acquire().await.unwrap();
+    let started = Instant::now();
+    let v = run_shell_command_limited(
+        &check.command,
+        timeout_ms,
+        Some("dev"),
+        Some(path),
+        MAX_COMMAND_OUTPUT_BYTES,
+    )
+    .await;
+    let (exit_code, stdout, stderr) = parse_exec_value(v);
+    GitCheckResult {
+        name: check.name,
+        source: check.source,
+        command: check.command,
+        passed: exit_code == 0,
+        exit_code,
+        duration_ms: started.elapsed().as_millis() as u64,
+        timed_out: stderr.contains("Command timed out\n"),
+        output: output_tail(&stdout, &stderr),
+    }
+}
+
+/// Runs the repo's checks one after another. Callers shouldn't hold
+/// `GIT_SEMAPHORE` meanwhile.
+pub(super) async fn run_checks(
+    path: &str,
+    repo_path: &str,
+    only: &[String],
+    all_files: bool,
+    timeout_ms: u64,
+) -> Vec<GitCheckResult> {
+    let mut results = Vec::new();
+    for check in detect_checks(path, repo_path, all_files) {
+        if !only.is_empty() && !only.contains(&check.name) {
+            continue;
+        }
+        results.push(run_check(path, check, timeout_ms).await);
+    }
+    results
+}
+
+pub async fn handle_git_checks(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: ChecksBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => {
+            return json_ok(
+                serde_json::to_value(GitChecksResponse {
+                    error: Some(e),
+                    ..Default::default()
+                })
+                .unwrap(),
+            );
+        }
+    };
+    let path = full_path(&body.repo_path);
+    if !Path::new(&path).is_dir() {
+        return json_ok(
+            serde_json::to_value(GitChecksResponse {
+                path: body.repo_path,
+                error: Some("Repository not found".to_string()),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+    }
+    let timeout_ms = body
+        .timeout_ms
+        .unwrap_or(DEFAULT_CHECK_TIMEOUT_MS)
+        .clamp(1000, MAX_CHECK_TIMEOUT_MS);
+
+    let checks = run_checks(
+        &path,
+        &body.repo_path,
+        &body.only,
+        body.all_files,
+        timeout_ms,
+    )
+    .await;
+    json_ok(
+        serde_json::to_value(GitChecksResponse {
+            path: body.repo_path,
+            passed: checks.iter().all(|c| c.passed),
+            checks,
+            error: None,
+        })
+        .unwrap(),
+    )
+}
@@ -0,0 +1,529 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::sync::watch;
+
+use super::{GitRepoStatus, default_true, full_path, get_repo_status, read_json_body};
+use crate::limits::{CLONE_SEMAPHORE, GIT_SEMAPHORE};
+use crate::path_policy::{PathAccess, resolve};
+use crate::response::{json_error, json_ok};
+use crate::utc_rfc3339;
+
+const DEFAULT_CLONE_TIMEOUT_MS: u64 = 30 * 60 * 1000;
+// Finished operations stay queryable this long, so a poller that missed the
+// end still gets the result.
+const FINISHED_RETENTION: Duration = Duration::from_secs(60 * 60);
+// Tail of git's stderr kept for the error message of a failed clone.
+const STDERR_TAIL_BYTES: usize = 8 * 1024;
+const DEFAULT_STATUS_WAIT_MS: u64 = 25_000;
+const MAX_STATUS_WAIT_MS: u64 = 60_000;
+
+static CLONE_OPERATIONS: LazyLock<Mutex<HashMap<String, CloneOperation>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+// Bumped on every operation update, waking status requests that wait for one.
+static CLONE_UPDATES: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::Sender::new(0));
+/// Destinations of clones queued or running; a second clone into one is
+/// refused rather than racing the first (and cleaning up after it).
+static CLONE_TARGETS: LazyLock<Mutex<HashSet<String>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Holds a destination in `CLONE_TARGETS` until the clone finishes.
+struct ClaimedTarget(String);
+
+impl ClaimedTarget {
+    fn claim(target: &str) -> Option<Self> {
+        CLONE_TARGETS
+            .lock()
+            .unwrap()
+            .insert(target.to_string())
+            .then(|| Self(target.to_string()))
+    }
+}
+
+impl Drop for ClaimedTarget {
+    fn drop(&mut self) {
+        CLONE_TARGETS.lock().unwrap().remove(&self.0);
+    }
+}
+
+fn is_non_empty_dir(path: &str) -> bool {
+    std::fs::read_dir(path).is_ok_and(|mut d| d.next().is_some())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CloneBody {
+    url: String,
+    clone_path: String,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    depth: Option<u32>,
+    #[serde(default)]
+    single_branch: bool,
+    /// Partial clone without blobs (`--filter=blob:none`); blobs are fetched
+    /// on demand at checkout.
+    #[serde(default)]
+    blobless: bool,
+    #[serde(default)]
+    sparse_paths: Vec<String>,
+    #[serde(default = "default_true")]
+    sparse_cone: bool,
+    #[serde(default)]
+    submodules: bool,
+    #[serde(default)]
+    shallow_submodules: bool,
+    /// Download Git LFS objects in one batch after checkout rather than one
+    /// by one while checking out.
+    #[serde(default)]
+    lfs: bool,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CloneState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloneProgress {
+    phase: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percent: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CloneOperation {
+    id: String,
+    url: String,
+    clone_path: String,
+    state: CloneState,
+    /// Bumped on every update; pass as `since` to wait for the next one.
+    version: u64,
+    /// One entry per phase seen so far, the last one being current.
+    phases: Vec<CloneProgress>,
+    started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checkout: Option<GitRepoStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip)]
+    finished: Option<Instant>,
+}
+
+fn generate_operation_id() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("clone_{:x}", nanos)
+}
+
+fn update_operation(id: &str, f: impl FnOnce(&mut CloneOperation)) {
+    if let Some(op) = CLONE_OPERATIONS.lock().unwrap().get_mut(id) {
+        f(op);
+        op.version += 1;
+        CLONE_UPDATES.send_modify(|n| *n += 1);
+    }
+}
+
+fn finish_operation(id: &str, result: Result<GitRepoStatus, String>) {
+    update_operation(id, |op| {
+        op.finished = Some(Instant::now());
+        op.finished_at = Some(utc_rfc3339());
+        match result {
+            Ok(status) => {
+                op.state = CloneState::Succeeded;
+                op.checkout = Some(status);
+            }
+            Err(e) => {
+                op.state = CloneState::Failed;
+                op.error = Some(e);
+            }
+        }
+    });
+}
+
+/// Maps a git progress label to its phase name.
+fn phase_name(label: &str) -> Option<&'static str> {
+    match label {
+        "Enumerating objects" | "Counting objects" => Some("counting"),
+        "Compressing objects" => Some("compressing"),
+        "Receiving objects" => Some("receiving"),
+        "Resolving deltas" => Some("resolving"),
+        "Updating files" => Some("checkout"),
+        "Filtering content" => Some("filtering"),
+        "Downloading LFS objects" => Some("lfs"),
+        _ => None,
+    }
+}
+
+/// Parses one progress line, e.g.
+/// `remote: Counting objects:  45% (450/1000)` or
+/// `Receiving objects:  12% (120/1000), 1.20 MiB | 2.40 MiB/s`.
+fn parse_progress(line: &str) -> Option<CloneProgress> {
+    let line = line.trim().trim_start_matches("remote: ");
+    let (label, rest) = line.split_once(':')?;
+    let phase = phase_name(label.trim())?;
+    let rest = rest.trim();
+
+    let percent = rest
+        .split_once('%')
+        .and_then(|(p, _)| p.trim().parse::<u8>().ok());
+    let counts = rest
+        .split_once('(')
+        .and_then(|(_, r)| r.split_once(')'))
+        .and_then(|(c, _)| c.split_once('/'));
+    let (current, total) = match counts {
+        Some((c, t)) => (c.trim().parse().ok(), t.trim().parse().ok()),
+        // "Counting objects: 1234, done." with no total.
+        None => (
+            rest.split(|c: char| !c.is_ascii_digit())
+                .next()
+                .and_then(|n| n.parse().ok()),
+            None,
+        ),
+    };
+
+    Some(CloneProgress {
+        phase,
+        percent,
+        current,
+        total,
+    })
+}
+
+fn record_progress(id: &str, progress: CloneProgress) {
+    update_operation(id, |op| match op.phases.last_mut() {
+        Some(last) if last.phase == progress.phase => *last = progress,
+        _ => op.phases.push(progress),
+    });
+}
+
+fn dev_git(args: &[String], dir: Option<&str>) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.args(args)
+        .uid(1000)
+        .gid(1000)
+        .env("HOME", "/home/dev")
+        .env("USER", "dev")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .envs(crate::git_credential::helper_env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        // Own group, so a timeout also stops the helpers git spawns
+        // (remote-https, index-pack) before the directory is removed.
+        .process_group(0)
+        .kill_on_drop(true);
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    cmd
+}
+
+/// Kills a process group when dropped, as the clone future is on timeout.
+struct KillGroupOnDrop(Option<u32>);
+
+impl Drop for KillGroupOnDrop {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0 {
+            unsafe { libc::kill(-(pgid as i32), libc::SIGKILL) };
+        }
+    }
+}
+
+/// Runs git, feeding progress lines to the operation. Returns the stderr
+/// tail on failure.
+async fn run_git_with_progress(id: &str, mut cmd: Command) -> Result<(), String> {
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to run git: {e}"))?;
+    let mut stderr = child.stderr.take().expect("stderr piped");
+    let mut group = KillGroupOnDrop(child.id());
+
+    let mut tail: Vec<u8> = Vec::new();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match stderr.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        for &b in &buf[..n] {
+            // Progress redraws end in '\r', final lines in '\n'.
+            if b == b'\r' || b == b'\n' {
+                if let Some(progress) = parse_progress(&String::from_utf8_lossy(&pending)) {
+                    record_progress(id, progress);
+                } else if !pending.is_empty() {
+                    tail.extend_from_slice(&pending);
+                    tail.push(b'\n');
+                }
+                pending.clear();
+            } else {
+                pending.push(b);
+            }
+        }
+        if tail.len() > STDERR_TAIL_BYTES {
+            tail.drain(..tail.len() - STDERR_TAIL_BYTES);
+        }
+    }
+    tail.extend_from_slice(&pending);
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("Failed to wait for git: {e}"))?;
+    group.0 = None;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&tail).trim().to_string())
+    }
+}
+
+async fn run_clone(id: &str, body: &CloneBody, target: &str) -> Result<(), String> {
+    let git = |args: &[String], dir: Option<&str>| {
+        let mut cmd = dev_git(args, dir);
+        if body.lfs {
+            // Leave pointers at checkout; `git lfs pull` fetches them below.
+            cmd.env("GIT_LFS_SKIP_SMUDGE", "1");
+        }
+        cmd
+    };
+
+    let mut args: Vec<String> = vec!["clone".into(), "--progress".into()];
+    if let Some(branch) = &body.branch {
+        args.extend(["--branch".into(), branch.clone()]);
+    }
+    if let Some(depth) = body.depth {
+        args.push(format!("--depth={depth}"));
+    }
+    if body.single_branch {
+        args.push("--single-branch".into());
+    }
+    if body.blobless {
+        args.push("--filter=blob:none".into());
+    }
+    if !body.sparse_paths.is_empty() {
+        // Check out nothing yet; the sparse patterns decide what lands.
+        args.push("--no-checkout".into());
+    }
+    if body.submodules && body.sparse_paths.is_empty() {
+        args.push("--recurse-submodules".into());
+    }
+    if body.shallow_submodules {
+        args.push("--shallow-submodules".into());
+    }
+    args.extend(["--".into(), body.url.clone(), target.to_string()]);
+    run_git_with_progress(id, git(&args, None)).await?;
+
+    if !body.sparse_paths.is_empty() {
+        checkout_sparse(id, body, target, git).await?;
+    }
+    if body.lfs {
+        let pull: Vec<String> = vec!["lfs".into(), "pull".into()];
+        run_git_with_progress(id, dev_git(&pull, Some(target))).await?;
+        if body.submodules {
+            let foreach: Vec<String> = vec![
+                "submodule".into(),
+                "foreach".into(),
+                "--quiet".into(),
+                "--recursive".into(),
+                "git lfs pull".into(),
+            ];
+            run_git_with_progress(id, dev_git(&foreach, Some(target))).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn checkout_sparse(
+    id: &str,
+    body: &CloneBody,
+    target: &str,
+    git: impl Fn(&[String], Option<&str>) -> Command,
+) -> Result<(), String> {
+    let mut sparse: Vec<String> = vec!["sparse-checkout".into(), "set".into()];
+    if !body.sparse_cone {
+        sparse.push("--no-cone".into());
+    }
+    sparse.push("--".into());
+    sparse.extend(body.sparse_paths.iter().cloned());
+    run_git_with_progress(id, git(&sparse, Some(target))).await?;
+
+    let checkout: Vec<String> = vec!["checkout".into(), "--progress".into()];
+    run_git_with_progress(id, git(&checkout, Some(target))).await?;
+
+    if body.submodules {
+        let update: Vec<String> = vec![
+            "submodule".into(),
+            "update".into(),
+            "--init".into(),
+            "--recursive".into(),
+            "--progress".into(),
+        ];
+        run_git_with_progress(id, git(&update, Some(target))).await?;
+    }
+    Ok(())
+}
+
+/// Removes what a failed clone left behind, so the same path can be cloned
+/// into again. A destination that existed beforehand (empty, as checked once
+/// the clone got its slot) is kept and only emptied.
+fn remove_partial_clone(target: &str, existed: bool) {
+    if !existed {
+        let _ = std::fs::remove_dir_all(target);
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(target) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let _ = if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+    }
+}
+
+async fn clone_in_background(id: String, body: CloneBody, target: ClaimedTarget) {
+    let _permit = CLONE_SEMAPHORE.acquire().await.unwrap();
+    let target = &target.0;
+    // Something may have been written there while this clone was queued.
+    if is_non_empty_dir(target) {
+        let error = format!("Destination is not empty: {}", body.clone_path);
+        finish_operation(&id, Err(error));
+        return;
+    }
+    let existed = Path::new(target).exists();
+    let timeout = Duration::from_millis(
+        body.timeout_ms
+            .unwrap_or(DEFAULT_CLONE_TIMEOUT_MS)
+            .max(1000),
+    );
+
+    let result = match tokio::time::timeout(timeout, run_clone(&id, &body, target)).await {
+        Ok(Ok(())) => {
+            let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+            Ok(get_repo_status(body.clone_path.clone()).await)
+        }
+        Ok(Err(e)) => Err(e),
+        // Dropping the future kills git's process group.
+        Err(_) => Err(format!("Clone timed out after {}ms", timeout.as_millis())),
+    };
+    if result.is_err() {
+        remove_partial_clone(target, existed);
+    }
+    finish_operation(&id, result);
+}
+
+fn prune_finished(ops: &mut HashMap<String, CloneOperation>) {
+    ops.retain(|_, op| op.finished.is_none_or(|t| t.elapsed() < FINISHED_RETENTION));
+}
+
+pub async fn handle_git_clone(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: CloneBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &e),
+    };
+    if body.url.is_empty() || body.url.starts_with('-') {
+        return json_error(StatusCode::BAD_REQUEST, "Invalid repository URL");
+    }
+
+    let target = match resolve(&full_path(&body.clone_path), PathAccess::Write) {
+        Ok(p) => p.to_string_lossy().into_owned(),
+        Err(e) => return json_error(StatusCode::BAD_REQUEST, &e),
+    };
+    if is_non_empty_dir(&target) {
+        return json_error(
+            StatusCode::CONFLICT,
+            &format!("Destination is not empty: {}", body.clone_path),
+        );
+    }
+    let Some(target) = ClaimedTarget::claim(&target) else {
+        return json_error(
+            StatusCode::CONFLICT,
+            &format!("A clone into {} is already running", body.clone_path),
+        );
+    };
+
+    let id = generate_operation_id();
+    let op = CloneOperation {
+        id: id.clone(),
+        url: body.url.clone(),
+        clone_path: body.clone_path.clone(),
+        state: CloneState::Running,
+        version: 0,
+        phases: Vec::new(),
+        started_at: utc_rfc3339(),
+        finished_at: None,
+        checkout: None,
+        error: None,
+        finished: None,
+    };
+    {
+        let mut ops = CLONE_OPERATIONS.lock().unwrap();
+        prune_finished(&mut ops);
+        ops.insert(id.clone(), op.clone());
+    }
+
+    tokio::spawn(clone_in_background(id, body, target));
+    json_ok(serde_json::to_value(op).unwrap())
+}
+
+/// `GET /git/clone/{id}?since=<version>&timeoutMs=<ms>`: the operation, once
+/// its `version` is past `since`, waiting up to `timeoutMs` for progress when
+/// it isn't yet. Without `since`, returns straight away.
+pub async fn handle_git_clone_status(id: &str, query: &str) -> Response<Full<Bytes>> {
+    let mut since: Option<u64> = None;
+    let mut timeout_ms = DEFAULT_STATUS_WAIT_MS;
+    for param in query.split('&') {
+        let mut kv = param.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("since"), Some(v)) => since = v.parse().ok(),
+            (Some("timeoutMs"), Some(v)) => timeout_ms = v.parse().unwrap_or(timeout_ms),
+            _ => {}
+        }
+    }
+    let deadline =
+        tokio::time::Instant::now() + Duration::from_millis(timeout_ms.min(MAX_STATUS_WAIT_MS));
+
+    let mut updates = CLONE_UPDATES.subscribe();
+    loop {
+        updates.borrow_and_update();
+        let Some(op) = CLONE_OPERATIONS.lock().unwrap().get(id).cloned() else {
+            return json_error(StatusCode::NOT_FOUND, "Clone operation not found");
+        };
+        let waiting = since.is_some_and(|s| op.version <= s) && op.state == CloneState::Running;
+        if !waiting
+            || tokio::time::timeout_at(deadline, updates.changed())
+                .await
+                .is_err()
+        {
+            return json_ok(serde_json::to_value(op).unwrap());
+        }
+    }
+}
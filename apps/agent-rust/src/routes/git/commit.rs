@@ -0,0 +1,377 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::checks::{DEFAULT_CHECK_TIMEOUT_MS, GitCheckResult, run_checks};
+use super::{full_path, read_json_body, run_as_dev, shell_quote};
+use crate::limits::GIT_SEMAPHORE;
+use crate::response::json_ok;
+
+// Hooks `git commit` runs before the commit exists, in order.
+const COMMIT_HOOKS: [&str; 3] = ["pre-commit", "prepare-commit-msg", "commit-msg"];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Identity {
+    name: String,
+    email: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitBody {
+    repo_path: String,
+    #[serde(default)]
+    message: String,
+    /// Commit only these paths (staging them first), leaving other changes
+    /// in the tree and index alone.
+    #[serde(default)]
+    paths: Vec<String>,
+    /// Stage everything before committing. Defaults to true when no `paths`
+    /// are given; `false` commits the index as it is.
+    #[serde(default)]
+    all: Option<bool>,
+    #[serde(default)]
+    amend: bool,
+    #[serde(default)]
+    allow_empty: bool,
+    #[serde(default)]
+    author: Option<Identity>,
+    #[serde(default)]
+    committer: Option<Identity>,
+    #[serde(default)]
+    signoff: bool,
+    #[serde(default)]
+    no_verify: bool,
+    /// Run the repo's checks (see `/git/checks`) on the staged changes
+    /// first; the commit is skipped if any fail, leaving the changes staged.
+    #[serde(default)]
+    run_checks: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitHookFailure {
+    hook: &'static str,
+    output: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitCommitResponse {
+    path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_hash: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    files: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hook_failure: Option<GitHookFailure>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checks: Option<Vec<GitCheckResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitCommitResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+fn identity_arg(identity: &Identity) -> String {
+    shell_quote(&format!("{} <{}>", identity.name, identity.email))
+}
+
+/// The repo's hooks directory. `--git-path hooks` follows `core.hooksPath`,
+/// which is where husky and similar tools put theirs.
+async fn hooks_dir(path: &str) -> Option<PathBuf> {
+    let (code, out, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --path-format=absolute --git-path hooks"
+    ))
+    .await;
+    (code == 0).then(|| PathBuf::from(out.trim()))
+}
+
+/// Executable commit hooks installed in `dir`.
+fn installed_hooks(dir: &Path) -> Vec<&'static str> {
+    COMMIT_HOOKS
+        .into_iter()
+        .filter(|hook| {
+            std::fs::metadata(dir.join(hook))
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+        .collect()
+}
+
+/// Absolute path of `name` inside the repo's git dir.
+async fn git_path(path: &str, name: &str) -> String {
+    let (_, out, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --path-format=absolute --git-path {name}"
+    ))
+    .await;
+    out.trim().to_string()
+}
+
+/// The last commit hook git started, from its `GIT_TRACE` output. Hooks run
+/// one after another and the first to fail stops the commit, so this is the
+/// one that refused.
+fn last_hook_run(trace: &str) -> Option<&'static str> {
+    trace
+        .lines()
+        .rev()
+        .filter_map(|l| l.split_once("trace: run_command: "))
+        .filter_map(|(_, cmd)| {
+            // `VAR=value ... <hook> [args]`
+            let program = cmd.split(' ').find(|w| !w.contains('='))?;
+            let name = Path::new(program.trim_matches('\'')).file_name()?.to_str()?;
+            COMMIT_HOOKS.into_iter().find(|h| *h == name)
+        })
+        .next()
+}
+
+/// Runs the commit-msg hook by hand, for a commit that skips its hooks
+/// because `runChecks` already ran pre-commit. Returns the file holding the
+/// message as the hook left it, or the hook's output when it refused.
+async fn run_commit_msg_hook(path: &str, message: &str) -> Result<String, String> {
+    let file = git_path(path, "ATELIER_COMMIT_MSG").await;
+    let quoted = shell_quote(&file);
+    let write = if message.trim().is_empty() {
+        // `--no-edit` amend: the message being kept.
+        format!("git -C '{path}' log -1 --format=%B HEAD > {quoted}")
+    } else {
+        format!("printf '%s\\n' {} > {quoted}", shell_quote(message))
+    };
+    let (code, stdout, stderr) = run_as_dev(&format!(
+        "{write} && git -C '{path}' hook run commit-msg -- {quoted}"
+    ))
+    .await;
+    if code != 0 {
+        let _ = std::fs::remove_file(&file);
+        return Err(format!("{stdout}{stderr}").trim().to_string());
+    }
+    Ok(file)
+}
+
+/// Failures git reports itself; anything else from a failed commit with
+/// hooks installed came from a hook. The commit runs with `LC_ALL=C`, so
+/// these are never translated.
+fn is_git_failure(output: &str) -> bool {
+    output.lines().any(|l| l.starts_with("fatal: "))
+        || output.contains("nothing to commit")
+        || output.contains("nothing added to commit")
+        || output.contains("no changes added to commit")
+        || output.contains("Aborting commit due to empty commit message")
+}
+
+async fn commit(body: CommitBody) -> GitCommitResponse {
+    let path = full_path(&body.repo_path);
+
+    if body.message.trim().is_empty() && !body.amend {
+        return GitCommitResponse::failed(body.repo_path, "Commit message is required");
+    }
+
+    let pathspec: String = if body.paths.is_empty() {
+        String::new()
+    } else {
+        let quoted: Vec<String> = body.paths.iter().map(|p| shell_quote(p)).collect();
+        format!(" -- {}", quoted.join(" "))
+    };
+
+    let stage = if !body.paths.is_empty() {
+        format!("git -C '{path}' add -A{pathspec} && ")
+    } else if body.all.unwrap_or(true) {
+        format!("git -C '{path}' add -A && ")
+    } else {
+        String::new()
+    };
+
+    let checks = if body.run_checks {
+        if !stage.is_empty() {
+            let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+            let (code, _, stderr) = run_as_dev(stage.trim_end_matches(" && ")).await;
+            if code != 0 {
+                return GitCommitResponse::failed(body.repo_path, stderr.trim());
+            }
+        }
+        let checks = run_checks(&path, &body.repo_path, &[], false, DEFAULT_CHECK_TIMEOUT_MS).await;
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.as_str())
+            .collect();
+        if !failed.is_empty() {
+            let error = format!("Checks failed: {}", failed.join(", "));
+            return GitCommitResponse {
+                checks: Some(checks),
+                ..GitCommitResponse::failed(body.repo_path, error)
+            };
+        }
+        Some(checks)
+    } else {
+        None
+    };
+
+    // Taken only now so the checks above don't hold a git slot while they run.
+    let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+    let hooks_dir = if body.no_verify {
+        None
+    } else {
+        hooks_dir(&path).await
+    };
+    let hooks = hooks_dir
+        .as_deref()
+        .map(installed_hooks)
+        .unwrap_or_default();
+    // When the installed pre-commit hook is one the checks just ran,
+    // `--no-verify` keeps git from running it again. That also skips
+    // commit-msg, which is run here instead. Any other pre-commit hook runs
+    // as usual.
+    let skip_pre_commit = match (&hooks_dir, &checks) {
+        (Some(dir), Some(checks)) if hooks.contains(&"pre-commit") => {
+            let hook = dir.join("pre-commit");
+            checks.iter().any(|c| c.runs_hook(Path::new(&path), &hook))
+        }
+        _ => false,
+    };
+    let mut message_file = None;
+    if skip_pre_commit && hooks.contains(&"commit-msg") {
+        match run_commit_msg_hook(&path, &body.message).await {
+            Ok(file) => message_file = Some(file),
+            Err(output) => {
+                return GitCommitResponse {
+                    hook_failure: Some(GitHookFailure {
+                        hook: "commit-msg",
+                        output,
+                    }),
+                    checks,
+                    ..GitCommitResponse::failed(body.repo_path, "commit-msg hook failed")
+                };
+            }
+        }
+    }
+
+    let mut flags = String::new();
+    if let Some(file) = &message_file {
+        flags.push_str(&format!(" -F {}", shell_quote(file)));
+    } else if body.message.trim().is_empty() {
+        flags.push_str(" --no-edit");
+    } else {
+        flags.push_str(&format!(" -m {}", shell_quote(&body.message)));
+    }
+    if body.amend {
+        flags.push_str(" --amend");
+    }
+    if body.allow_empty {
+        flags.push_str(" --allow-empty");
+    }
+    if body.signoff {
+        flags.push_str(" --signoff");
+    }
+    if body.no_verify || skip_pre_commit {
+        flags.push_str(" --no-verify");
+    }
+    if let Some(author) = &body.author {
+        flags.push_str(&format!(" --author={}", identity_arg(author)));
+    }
+    let env = match &body.committer {
+        Some(c) => format!(
+            "GIT_COMMITTER_NAME={} GIT_COMMITTER_EMAIL={} ",
+            shell_quote(&c.name),
+            shell_quote(&c.email)
+        ),
+        None => String::new(),
+    };
+
+    // Git's trace names every hook it runs, which tells which one refused.
+    let trace = if hooks.is_empty() {
+        None
+    } else {
+        Some(git_path(&path, "ATELIER_COMMIT_TRACE").await)
+    };
+    let trace_env = trace
+        .as_deref()
+        .map(|t| format!("GIT_TRACE={} ", shell_quote(t)))
+        .unwrap_or_default();
+
+    let cmd = format!("{stage}{env}{trace_env}LC_ALL=C git -C '{path}' commit{flags}{pathspec}");
+    let (code, stdout, stderr) = run_as_dev(&cmd).await;
+    let hook_run = trace.as_deref().and_then(|t| {
+        let text = std::fs::read_to_string(t).ok();
+        let _ = std::fs::remove_file(t);
+        last_hook_run(&text?)
+    });
+    if let Some(file) = &message_file {
+        let _ = std::fs::remove_file(file);
+    }
+
+    if code != 0 {
+        let output = format!("{stdout}{stderr}").trim().to_string();
+        if let Some(hook) = hook_run.filter(|_| !is_git_failure(&output)) {
+            return GitCommitResponse {
+                hook_failure: Some(GitHookFailure {
+                    hook,
+                    output,
+                }),
+                ..GitCommitResponse::failed(body.repo_path, format!("{hook} hook failed"))
+            };
+        }
+        let error = if stderr.trim().is_empty() {
+            stdout.trim().to_string()
+        } else {
+            stderr.trim().to_string()
+        };
+        return GitCommitResponse::failed(body.repo_path, error);
+    }
+
+    let (_, hash_out, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse HEAD && git -C '{path}' rev-parse --short HEAD"
+    ))
+    .await;
+    let mut hashes = hash_out.lines().map(|l| l.trim().to_string());
+    let full_hash = hashes.next();
+    let hash = hashes.next();
+
+    let (_, files_out, _) = run_as_dev(&format!(
+        "git -C '{path}' diff-tree --root --no-commit-id --name-only -r -z HEAD"
+    ))
+    .await;
+
+    GitCommitResponse {
+        path: body.repo_path,
+        success: true,
+        hash,
+        full_hash,
+        files: files_out
+            .split('\0')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect(),
+        hook_failure: None,
+        checks,
+        error: None,
+    }
+}
+
+pub async fn handle_git_commit(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: CommitBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => {
+            return json_ok(
+                serde_json::to_value(GitCommitResponse::failed(String::new(), e)).unwrap(),
+            );
+        }
+    };
+
+    json_ok(serde_json::to_value(commit(body).await).unwrap())
+}
@@ -0,0 +1,256 @@
+// "Everything this task changed": the work since the merge-base with the
+// branch the repo was cloned from, committed or not.
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::status_detail::count_lines;
+use super::{
+    GitDiffFile, full_path, handle_json_body, run_as_dev, run_as_dev_timeout, shell_quote,
+};
+use crate::config::{GIT_NETWORK_TIMEOUT_MS, get_config};
+use crate::limits::{GIT_NETWORK_SEMAPHORE, GIT_SEMAPHORE};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompareBody {
+    repo_path: String,
+    /// Defaults to `origin/<branch>` from the sandbox's repo config, then
+    /// the current branch's upstream.
+    #[serde(default)]
+    base: Option<String>,
+    /// Fetch the base's remote branch first, so `baseMoved` is current.
+    #[serde(default)]
+    fetch: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitCompareCommit {
+    hash: String,
+    short_hash: String,
+    author: String,
+    date: String,
+    subject: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitCompareResponse {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merge_base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_head: Option<String>,
+    /// The base has commits the work doesn't include yet.
+    base_moved: bool,
+    /// Number of those commits.
+    behind: u32,
+    commits: Vec<GitCompareCommit>,
+    /// Merge-base against the working tree: committed, staged, unstaged and
+    /// untracked changes together.
+    files: Vec<GitDiffFile>,
+    total_added: u32,
+    total_removed: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fetch_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitCompareResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+async fn rev_parse(path: &str, rev: &str) -> Option<String> {
+    let (code, out, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --verify --quiet {}",
+        shell_quote(&format!("{rev}^{{commit}}"))
+    ))
+    .await;
+    (code == 0).then(|| out.trim().to_string())
+}
+
+/// The configured base for a repo, falling back to its upstream.
+async fn default_base(path: &str, repo_path: &str) -> Option<String> {
+    let configured = get_config().and_then(|c| {
+        c.repos
+            .into_iter()
+            .find(|r| r.clone_path.trim_end_matches('/') == repo_path.trim_end_matches('/'))
+    });
+    if let Some(repo) = configured {
+        return Some(format!("origin/{}", repo.branch));
+    }
+    let (code, out, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --abbrev-ref --symbolic-full-name '@{{upstream}}'"
+    ))
+    .await;
+    (code == 0).then(|| out.trim().to_string())
+}
+
+/// Fetches the remote branch behind a remote-tracking base; other refs have
+/// nothing to fetch.
+async fn fetch_base(path: &str, base: &str) -> Result<(), String> {
+    let (code, full_name, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-parse --symbolic-full-name {}",
+        shell_quote(base)
+    ))
+    .await;
+    let full_name = full_name.trim();
+    let Some((remote, branch)) = full_name
+        .strip_prefix("refs/remotes/")
+        .and_then(|r| r.split_once('/'))
+        .filter(|_| code == 0)
+    else {
+        return Ok(());
+    };
+    let _permit = GIT_NETWORK_SEMAPHORE.acquire().await.unwrap();
+    let (code, _, stderr) = run_as_dev_timeout(
+        &format!(
+            "GIT_TERMINAL_PROMPT=0 git -C '{path}' fetch --quiet {} {}",
+            shell_quote(remote),
+            shell_quote(&format!("+refs/heads/{branch}:{full_name}"))
+        ),
+        GIT_NETWORK_TIMEOUT_MS,
+    )
+    .await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(())
+}
+
+async fn commits_since(path: &str, merge_base: &str) -> Vec<GitCompareCommit> {
+    let (_, out, _) = run_as_dev(&format!(
+        "git -C '{path}' log --format='%H%x00%h%x00%an%x00%aI%x00%s' {merge_base}..HEAD"
+    ))
+    .await;
+    out.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\0').collect();
+            let [hash, short_hash, author, date, subject] = fields.as_slice() else {
+                return None;
+            };
+            Some(GitCompareCommit {
+                hash: hash.to_string(),
+                short_hash: short_hash.to_string(),
+                author: author.to_string(),
+                date: date.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect()
+}
+
+async fn combined_diffstat(path: &str, merge_base: &str) -> Vec<GitDiffFile> {
+    // Without --cached, `git diff <commit>` compares against the working
+    // tree, so staged and unstaged edits are both in.
+    let (_, out, _) = run_as_dev(&format!(
+        "git -C '{path}' diff --numstat -z --no-renames {merge_base}"
+    ))
+    .await;
+    let mut files: Vec<GitDiffFile> = out
+        .split('\0')
+        .filter_map(|entry| {
+            let mut parts = entry.splitn(3, '\t');
+            let (added, removed, file) = (parts.next()?, parts.next()?, parts.next()?);
+            Some(GitDiffFile {
+                path: file.to_string(),
+                added: added.parse().unwrap_or(0),
+                removed: removed.parse().unwrap_or(0),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    let (_, untracked, _) = run_as_dev(&format!(
+        "git -C '{path}' ls-files -z --others --exclude-standard"
+    ))
+    .await;
+    for file in untracked.split('\0').filter(|f| !f.is_empty()) {
+        let (lines, _) = count_lines(&std::path::Path::new(path).join(file));
+        files.push(GitDiffFile {
+            path: file.to_string(),
+            added: lines.unwrap_or(0),
+            removed: 0,
+            ..Default::default()
+        });
+    }
+    files
+}
+
+async fn compare(body: CompareBody) -> GitCompareResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitCompareResponse {
+        path: body.repo_path.clone(),
+        ..Default::default()
+    };
+
+    let base = match body.base.filter(|b| !b.is_empty()) {
+        Some(base) => base,
+        None => match default_base(&path, &body.repo_path).await {
+            Some(base) => base,
+            None => {
+                resp.error = Some("No base configured and no upstream branch".to_string());
+                return resp;
+            }
+        },
+    };
+    if base.starts_with('-') {
+        resp.error = Some(format!("Invalid base: {base}"));
+        return resp;
+    }
+    resp.base = Some(base.clone());
+
+    if body.fetch
+        && let Err(e) = fetch_base(&path, &base).await
+    {
+        resp.fetch_error = Some(e);
+    }
+    // Taken after the fetch, which waits on the remote under its own limit.
+    let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+
+    let Some(base_head) = rev_parse(&path, &base).await else {
+        resp.error = Some(format!("Unknown base: {base}"));
+        return resp;
+    };
+    let (code, merge_base, stderr) =
+        run_as_dev(&format!("git -C '{path}' merge-base HEAD {base_head}")).await;
+    if code != 0 {
+        resp.error = Some(if stderr.trim().is_empty() {
+            format!("No common ancestor with {base}")
+        } else {
+            stderr.trim().to_string()
+        });
+        return resp;
+    }
+    let merge_base = merge_base.trim().to_string();
+
+    let (_, behind, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-list --count {merge_base}..{base_head}"
+    ))
+    .await;
+    resp.behind = behind.trim().parse().unwrap_or(0);
+    resp.base_moved = base_head != merge_base;
+    resp.commits = commits_since(&path, &merge_base).await;
+    resp.files = combined_diffstat(&path, &merge_base).await;
+    resp.total_added = resp.files.iter().map(|f| f.added).sum();
+    resp.total_removed = resp.files.iter().map(|f| f.removed).sum();
+    resp.merge_base = Some(merge_base);
+    resp.base_head = Some(base_head);
+    resp
+}
+
+pub async fn handle_git_compare(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_body(req, GitCompareResponse::failed, compare).await
+}
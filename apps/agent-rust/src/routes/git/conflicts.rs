@@ -0,0 +1,461 @@
+// Conflict inspection and resolution for a stopped merge, rebase,
+// cherry-pick or revert (or a stash apply, which conflicts without one).
+// "ours" and "theirs" are git's index stages 2 and 3: during a rebase, ours
+// is the branch being rebased onto and theirs the commit being replayed.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    conflicted_files, default_true, full_path, handle_json_request, operation_in_progress,
+    run_as_dev, shell_quote,
+};
+
+// Blob versions larger than this are listed without content.
+const MAX_BLOB_BYTES: u64 = 1024 * 1024;
+// Same heuristic as git: a NUL in the first 8000 bytes means binary.
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConflictsBody {
+    repo_path: String,
+    /// Include base/ours/theirs blob contents and hunks.
+    #[serde(default = "default_true")]
+    content: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Resolution {
+    Ours,
+    Theirs,
+    /// The file was edited by hand; stage it as is.
+    Resolved,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResolveBody {
+    repo_path: String,
+    path: String,
+    resolution: Resolution,
+    /// Stage a `resolved` file even if conflict markers remain.
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OperationBody {
+    repo_path: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitConflictVersion {
+    hash: String,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    binary: bool,
+}
+
+/// One `<<<<<<<` … `>>>>>>>` block in the working tree file. Lines are
+/// 1-based and include the markers.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitConflictHunk {
+    start_line: u32,
+    end_line: u32,
+    ours: String,
+    /// Only with `merge.conflictStyle` diff3 or zdiff3.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base: Option<String>,
+    theirs: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitConflictFile {
+    path: String,
+    conflict: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base: Option<GitConflictVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ours: Option<GitConflictVersion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    theirs: Option<GitConflictVersion>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hunks: Vec<GitConflictHunk>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitConflictsResponse {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation: Option<&'static str>,
+    files: Vec<GitConflictFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitConflictActionResponse {
+    path: String,
+    success: bool,
+    /// Operation still in progress afterwards, e.g. a rebase stopping at
+    /// the next conflicting commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation: Option<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    conflicts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitConflictActionResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Index stages 1-3 of a conflicted path, by blob hash.
+#[derive(Default)]
+struct Stages {
+    base: Option<String>,
+    ours: Option<String>,
+    theirs: Option<String>,
+}
+
+impl Stages {
+    fn conflict(&self) -> &'static str {
+        match (
+            self.base.is_some(),
+            self.ours.is_some(),
+            self.theirs.is_some(),
+        ) {
+            (false, true, true) => "both_added",
+            (true, true, false) => "deleted_by_them",
+            (true, false, true) => "deleted_by_us",
+            (false, true, false) => "added_by_us",
+            (false, false, true) => "added_by_them",
+            (true, false, false) => "both_deleted",
+            _ => "both_modified",
+        }
+    }
+}
+
+/// Parses `ls-files -u -z`: "<mode> <hash> <stage>\t<path>" per stage.
+async fn unmerged_stages(path: &str) -> Result<Vec<(String, Stages)>, String> {
+    let (code, out, stderr) = run_as_dev(&format!("git -C '{path}' ls-files -u -z")).await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    let mut files: Vec<(String, Stages)> = Vec::new();
+    for entry in out.split('\0').filter(|e| !e.is_empty()) {
+        let Some((meta, file)) = entry.split_once('\t') else {
+            continue;
+        };
+        let mut meta = meta.split(' ');
+        let (Some(_), Some(hash), Some(stage)) = (meta.next(), meta.next(), meta.next()) else {
+            continue;
+        };
+        if files.last().is_none_or(|(f, _)| f != file) {
+            files.push((file.to_string(), Stages::default()));
+        }
+        let stages = &mut files.last_mut().expect("pushed above").1;
+        let slot = match stage {
+            "1" => &mut stages.base,
+            "2" => &mut stages.ours,
+            "3" => &mut stages.theirs,
+            _ => continue,
+        };
+        *slot = Some(hash.to_string());
+    }
+    Ok(files)
+}
+
+/// Blob sizes in one `cat-file --batch-check` call.
+async fn blob_sizes(path: &str, hashes: &[&str]) -> HashMap<String, u64> {
+    if hashes.is_empty() {
+        return HashMap::new();
+    }
+    let (_, out, _) = run_as_dev(&format!(
+        "printf '%s\\n' {} | git -C '{path}' cat-file --batch-check='%(objectname) %(objectsize)'",
+        hashes.join(" ")
+    ))
+    .await;
+    out.lines()
+        .filter_map(|l| l.split_once(' '))
+        .filter_map(|(hash, size)| Some((hash.to_string(), size.parse().ok()?)))
+        .collect()
+}
+
+async fn load_version(
+    path: &str,
+    hash: Option<&String>,
+    sizes: &HashMap<String, u64>,
+    with_content: bool,
+) -> Option<GitConflictVersion> {
+    let hash = hash?;
+    let size = sizes.get(hash).copied().unwrap_or(0);
+    let mut version = GitConflictVersion {
+        hash: hash.clone(),
+        size,
+        ..Default::default()
+    };
+    if with_content && size <= MAX_BLOB_BYTES {
+        let (code, out, _) = run_as_dev(&format!("git -C '{path}' cat-file blob {hash}")).await;
+        if code == 0 {
+            if out.as_bytes()[..out.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+                version.binary = true;
+            } else {
+                version.content = Some(out);
+            }
+        }
+    }
+    Some(version)
+}
+
+/// Conflict blocks in a working tree file, handling both the default
+/// `merge` style and diff3's `|||||||` base section.
+fn parse_hunks(text: &str) -> Vec<GitConflictHunk> {
+    enum Section {
+        Ours,
+        Base,
+        Theirs,
+    }
+    let mut hunks = Vec::new();
+    let mut open: Option<(u32, Section, String, Option<String>, String)> = None;
+
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let line_no = i as u32 + 1;
+        let marker = |m: &str| {
+            line.starts_with(m)
+                && line[m.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| c == ' ' || c == '\n' || c == '\r')
+        };
+        match open.as_mut() {
+            None if marker("<<<<<<<") => {
+                open = Some((line_no, Section::Ours, String::new(), None, String::new()));
+            }
+            None => {}
+            Some((_, section, _, base, _)) if marker("|||||||") => {
+                *section = Section::Base;
+                *base = Some(String::new());
+            }
+            Some((_, section, ..)) if marker("=======") => *section = Section::Theirs,
+            Some(_) if marker(">>>>>>>") => {
+                let (start_line, _, ours, base, theirs) = open.take().expect("matched Some");
+                hunks.push(GitConflictHunk {
+                    start_line,
+                    end_line: line_no,
+                    ours,
+                    base,
+                    theirs,
+                });
+            }
+            Some((_, section, ours, base, theirs)) => match section {
+                Section::Ours => ours.push_str(line),
+                Section::Base => base.get_or_insert_default().push_str(line),
+                Section::Theirs => theirs.push_str(line),
+            },
+        }
+    }
+    hunks
+}
+
+fn worktree_hunks(repo: &str, file: &str) -> Vec<GitConflictHunk> {
+    let Ok(bytes) = std::fs::read(Path::new(repo).join(file)) else {
+        return Vec::new();
+    };
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return Vec::new();
+    }
+    parse_hunks(&String::from_utf8_lossy(&bytes))
+}
+
+async fn list(body: ConflictsBody) -> GitConflictsResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitConflictsResponse {
+        path: body.repo_path,
+        operation: operation_in_progress(&path).await,
+        ..Default::default()
+    };
+
+    let stages = match unmerged_stages(&path).await {
+        Ok(s) => s,
+        Err(e) => {
+            resp.error = Some(e);
+            return resp;
+        }
+    };
+    let hashes: Vec<&str> = stages
+        .iter()
+        .flat_map(|(_, s)| [&s.base, &s.ours, &s.theirs])
+        .filter_map(|h| h.as_deref())
+        .collect();
+    let sizes = blob_sizes(&path, &hashes).await;
+
+    for (file, stage) in &stages {
+        resp.files.push(GitConflictFile {
+            path: file.clone(),
+            conflict: stage.conflict(),
+            base: load_version(&path, stage.base.as_ref(), &sizes, body.content).await,
+            ours: load_version(&path, stage.ours.as_ref(), &sizes, body.content).await,
+            theirs: load_version(&path, stage.theirs.as_ref(), &sizes, body.content).await,
+            hunks: if body.content {
+                worktree_hunks(&path, file)
+            } else {
+                Vec::new()
+            },
+        });
+    }
+    resp
+}
+
+async fn resolve(body: ResolveBody) -> GitConflictActionResponse {
+    let path = full_path(&body.repo_path);
+    let stages = match unmerged_stages(&path).await {
+        Ok(s) => s,
+        Err(e) => return GitConflictActionResponse::failed(body.repo_path, e),
+    };
+    let Some((_, stage)) = stages.iter().find(|(f, _)| *f == body.path) else {
+        return GitConflictActionResponse::failed(
+            body.repo_path,
+            format!("Not conflicted: {}", body.path),
+        );
+    };
+
+    let file = shell_quote(&body.path);
+    let cmd = match body.resolution {
+        Resolution::Resolved => {
+            if !body.force && !worktree_hunks(&path, &body.path).is_empty() {
+                return GitConflictActionResponse::failed(
+                    body.repo_path,
+                    format!("{} still contains conflict markers", body.path),
+                );
+            }
+            // -A also stages the file's removal if it was deleted.
+            format!("git -C '{path}' add -A -- {file}")
+        }
+        side => {
+            let (present, flag) = if side == Resolution::Ours {
+                (stage.ours.is_some(), "--ours")
+            } else {
+                (stage.theirs.is_some(), "--theirs")
+            };
+            if present {
+                format!(
+                    "git -C '{path}' checkout {flag} -- {file} && git -C '{path}' add -- {file}"
+                )
+            } else {
+                // That side deleted the file.
+                format!("git -C '{path}' rm --quiet -- {file}")
+            }
+        }
+    };
+
+    let (code, _, stderr) = run_as_dev(&cmd).await;
+    if code != 0 {
+        return GitConflictActionResponse::failed(body.repo_path, stderr.trim());
+    }
+    GitConflictActionResponse {
+        path: body.repo_path,
+        success: true,
+        operation: operation_in_progress(&path).await,
+        conflicts: conflicted_files(&path).await,
+        error: None,
+    }
+}
+
+async fn run_operation(body: OperationBody, action: &str) -> GitConflictActionResponse {
+    let path = full_path(&body.repo_path);
+    let Some(op) = operation_in_progress(&path).await else {
+        return GitConflictActionResponse::failed(body.repo_path, "No operation in progress");
+    };
+    if action == "continue" {
+        let remaining = conflicted_files(&path).await;
+        if !remaining.is_empty() {
+            return GitConflictActionResponse {
+                operation: Some(op),
+                conflicts: remaining,
+                ..GitConflictActionResponse::failed(body.repo_path, "Unresolved conflicts remain")
+            };
+        }
+    }
+
+    // GIT_EDITOR=true accepts the prepared commit message as is.
+    let (code, stdout, stderr) =
+        run_as_dev(&format!("GIT_EDITOR=true git -C '{path}' {op} --{action}")).await;
+    let operation = operation_in_progress(&path).await;
+    let conflicts = conflicted_files(&path).await;
+    let success = code == 0 && conflicts.is_empty();
+    let error = (!success).then(|| {
+        if !conflicts.is_empty() {
+            format!("{op} stopped with new conflicts")
+        } else if stderr.trim().is_empty() {
+            stdout.trim().to_string()
+        } else {
+            stderr.trim().to_string()
+        }
+    });
+    GitConflictActionResponse {
+        path: body.repo_path,
+        success,
+        operation,
+        conflicts,
+        error,
+    }
+}
+
+pub async fn handle_git_conflicts(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(
+        req,
+        |_, e| GitConflictsResponse {
+            error: Some(e),
+            ..Default::default()
+        },
+        list,
+    )
+    .await
+}
+
+pub async fn handle_git_conflicts_resolve(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitConflictActionResponse::failed, resolve).await
+}
+
+pub async fn handle_git_conflicts_continue(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitConflictActionResponse::failed, |body| {
+        run_operation(body, "continue")
+    })
+    .await
+}
+
+pub async fn handle_git_conflicts_abort(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitConflictActionResponse::failed, |body| {
+        run_operation(body, "abort")
+    })
+    .await
+}
@@ -0,0 +1,806 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use git2::{Delta, Diff, DiffFindOptions, DiffOptions, Patch, Repository};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{default_true, full_path, native, read_json_body, run_as_dev, shell_quote};
+use crate::limits::GIT_SEMAPHORE;
+use crate::response::json_ok;
+
+const DEFAULT_DIFF_BUDGET_BYTES: usize = 512 * 1024;
+const MAX_DIFF_BUDGET_BYTES: usize = 8 * 1024 * 1024;
+// Larger files are reported as binary (no hunks) rather than loaded whole.
+const MAX_DIFF_FILE_BYTES: i64 = 4 * 1024 * 1024;
+// Rough per-line and per-hunk JSON overhead, so the budget tracks the
+// response size rather than just the patch text.
+const LINE_OVERHEAD_BYTES: usize = 48;
+const HUNK_OVERHEAD_BYTES: usize = 96;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffContentBody {
+    repo_path: String,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default = "default_true")]
+    staged: bool,
+    #[serde(default = "default_true")]
+    unstaged: bool,
+    #[serde(default = "default_true")]
+    untracked: bool,
+    #[serde(default)]
+    context_lines: Option<u32>,
+    #[serde(default)]
+    max_bytes: Option<usize>,
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DiffSource {
+    Staged,
+    Unstaged,
+    Untracked,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffLineContent {
+    origin: char,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_lineno: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_lineno: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiffHunkContent {
+    header: String,
+    old_start: u32,
+    old_lines: u32,
+    new_start: u32,
+    new_lines: u32,
+    lines: Vec<DiffLineContent>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitFilePatch {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    source: DiffSource,
+    status: &'static str,
+    binary: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_mode: Option<String>,
+    hunks: Vec<DiffHunkContent>,
+    /// Lines (counted across hunks) an earlier page already returned; the
+    /// first hunk here is cut to the lines after them.
+    #[serde(skip_serializing_if = "is_zero")]
+    line_offset: usize,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitDiffContentResponse {
+    path: String,
+    files: Vec<GitFilePatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Files are returned ordered by (source, path); a cursor names the first
+/// file of the next page, so it stays valid when earlier files change, and
+/// the line of that file to resume at when the previous page cut it short.
+fn encode_cursor(source: DiffSource, line: usize, path: &str) -> String {
+    format!("{}:{line}:{path}", source as u8)
+}
+
+fn decode_cursor(cursor: &str) -> Option<(u8, usize, &str)> {
+    let (rank, rest) = cursor.split_once(':')?;
+    let (line, path) = rest.split_once(':')?;
+    Some((rank.parse().ok()?, line.parse().ok()?, path))
+}
+
+fn status_name(status: Delta) -> &'static str {
+    match status {
+        Delta::Added => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "typechange",
+        Delta::Untracked => "untracked",
+        Delta::Conflicted => "conflicted",
+        _ => "modified",
+    }
+}
+
+fn file_mode(mode: git2::FileMode) -> Option<String> {
+    match u32::from(mode) {
+        0 => None,
+        m => Some(format!("{m:o}")),
+    }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches(['\n', '\r'])
+        .to_string()
+}
+
+/// One file's whole patch, before a page cuts it down.
+struct FileDiff {
+    path: String,
+    old_path: Option<String>,
+    source: DiffSource,
+    status: &'static str,
+    binary: bool,
+    old_mode: Option<String>,
+    new_mode: Option<String>,
+    hunks: Vec<DiffHunkContent>,
+}
+
+/// A page entry: loaded from libgit2 only once the page reaches it, or
+/// already parsed from the CLI.
+enum PendingFile<'a> {
+    Native(&'a Diff<'a>, usize),
+    Parsed(FileDiff),
+}
+
+fn native_file(diff: &Diff, idx: usize, source: DiffSource) -> Result<FileDiff, git2::Error> {
+    let delta = diff.get_delta(idx).expect("delta index in range");
+    let new_path = delta.new_file().path().map(|p| p.to_string_lossy().into_owned());
+    let old_path = delta.old_file().path().map(|p| p.to_string_lossy().into_owned());
+    let path = new_path.clone().or(old_path.clone()).unwrap_or_default();
+
+    let patch = Patch::from_diff(diff, idx)?;
+    let binary = patch
+        .as_ref()
+        .is_none_or(|p| p.delta().flags().is_binary());
+
+    let mut file = FileDiff {
+        old_path: old_path.filter(|o| *o != path),
+        path,
+        source,
+        status: status_name(delta.status()),
+        binary,
+        old_mode: file_mode(delta.old_file().mode()),
+        new_mode: file_mode(delta.new_file().mode()),
+        hunks: Vec::new(),
+    };
+    let Some(patch) = patch.filter(|_| !binary) else {
+        return Ok(file);
+    };
+    for h in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(h)?;
+        let mut lines = Vec::with_capacity(line_count);
+        for l in 0..line_count {
+            let line = patch.line_in_hunk(h, l)?;
+            lines.push(DiffLineContent {
+                origin: line.origin(),
+                content: lossy(line.content()),
+                old_lineno: line.old_lineno(),
+                new_lineno: line.new_lineno(),
+            });
+        }
+        file.hunks.push(DiffHunkContent {
+            header: lossy(hunk.header()),
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            lines,
+        });
+    }
+    Ok(file)
+}
+
+/// Cuts one file's patch to the lines from `skip` on, keeping at most
+/// `budget` bytes of hunks (but always at least one line). Returns the
+/// patch, the bytes it accounts for and, when truncated, the line to resume
+/// at.
+fn file_patch(file: FileDiff, skip: usize, budget: usize) -> (GitFilePatch, usize, Option<usize>) {
+    let mut out = GitFilePatch {
+        path: file.path,
+        old_path: file.old_path,
+        source: file.source,
+        status: file.status,
+        binary: file.binary,
+        old_mode: file.old_mode,
+        new_mode: file.new_mode,
+        hunks: Vec::new(),
+        line_offset: skip,
+        truncated: false,
+    };
+    let mut used = out.path.len() + HUNK_OVERHEAD_BYTES;
+
+    // Index of the current line across all hunks.
+    let mut next_line = 0;
+    let mut resume_at = None;
+    for mut hunk in file.hunks {
+        let line_count = hunk.lines.len();
+        if next_line + line_count <= skip {
+            next_line += line_count;
+            continue;
+        }
+        used += hunk.header.len() + HUNK_OVERHEAD_BYTES;
+        let mut lines = Vec::with_capacity(line_count);
+        for line in std::mem::take(&mut hunk.lines) {
+            if next_line < skip {
+                next_line += 1;
+                continue;
+            }
+            used += line.content.len() + LINE_OVERHEAD_BYTES;
+            if used > budget && next_line > skip {
+                resume_at = Some(next_line);
+                break;
+            }
+            lines.push(line);
+            next_line += 1;
+        }
+        // A hunk cut before its first line is left whole to the next page.
+        if !lines.is_empty() || resume_at.is_none() {
+            hunk.lines = lines;
+            out.hunks.push(hunk);
+        }
+        if resume_at.is_some() {
+            out.truncated = true;
+            break;
+        }
+    }
+
+    (out, used, resume_at)
+}
+
+fn budget(body: &DiffContentBody) -> usize {
+    body.max_bytes
+        .unwrap_or(DEFAULT_DIFF_BUDGET_BYTES)
+        .clamp(1, MAX_DIFF_BUDGET_BYTES)
+}
+
+fn collect_native(
+    repo: &Repository,
+    body: &DiffContentBody,
+) -> Result<(Vec<GitFilePatch>, Option<String>), git2::Error> {
+    let make_opts = || {
+        let mut opts = DiffOptions::new();
+        opts.context_lines(body.context_lines.unwrap_or(3))
+            .max_size(MAX_DIFF_FILE_BYTES);
+        for p in &body.paths {
+            opts.pathspec(p);
+        }
+        opts
+    };
+
+    let mut diffs: Vec<Diff> = Vec::new();
+    if body.staged {
+        let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+        let mut staged = repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut make_opts()))?;
+        staged.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+        diffs.push(staged);
+    }
+    if body.unstaged || body.untracked {
+        let mut opts = make_opts();
+        if body.untracked {
+            opts.include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+        }
+        diffs.push(repo.diff_index_to_workdir(None, Some(&mut opts))?);
+    }
+
+    let mut entries: Vec<(DiffSource, String, PendingFile)> = Vec::new();
+    for (n, diff) in diffs.iter().enumerate() {
+        let is_staged_diff = body.staged && n == 0;
+        for (idx, delta) in diff.deltas().enumerate() {
+            let source = if is_staged_diff {
+                DiffSource::Staged
+            } else if delta.status() == Delta::Untracked {
+                DiffSource::Untracked
+            } else {
+                DiffSource::Unstaged
+            };
+            if (source == DiffSource::Unstaged && !body.unstaged)
+                || (source == DiffSource::Untracked && !body.untracked)
+            {
+                continue;
+            }
+            let path = delta
+                .new_file()
+                .path()
+                .or(delta.old_file().path())
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            entries.push((source, path, PendingFile::Native(diff, idx)));
+        }
+    }
+    page(entries, body)
+}
+
+/// Files are taken in (source, path) order from the cursor on until the
+/// budget runs out.
+fn page(
+    mut entries: Vec<(DiffSource, String, PendingFile)>,
+    body: &DiffContentBody,
+) -> Result<(Vec<GitFilePatch>, Option<String>), git2::Error> {
+    entries.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    let start = body.cursor.as_deref().and_then(decode_cursor);
+    let mut files = Vec::new();
+    let mut remaining = budget(body);
+
+    for (source, path, pending) in entries {
+        let mut skip = 0;
+        if let Some((rank, line, after)) = start {
+            match (source as u8, path.as_str()).cmp(&(rank, after)) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Equal => skip = line,
+                std::cmp::Ordering::Greater => {}
+            }
+        }
+        if !files.is_empty() && remaining == 0 {
+            return Ok((files, Some(encode_cursor(source, 0, &path))));
+        }
+        let file = match pending {
+            PendingFile::Native(diff, idx) => native_file(diff, idx, source)?,
+            PendingFile::Parsed(file) => file,
+        };
+        // The first file of a page is always returned, truncated if needed,
+        // and the next page picks it up where it stopped; later files must
+        // fit entirely or start the next page.
+        let (patch, used, resume_at) = file_patch(file, skip, remaining.max(1));
+        if !files.is_empty() && (patch.truncated || used > remaining) {
+            return Ok((files, Some(encode_cursor(source, 0, &path))));
+        }
+        remaining = remaining.saturating_sub(used);
+        files.push(patch);
+        if let Some(line) = resume_at {
+            return Ok((files, Some(encode_cursor(source, line, &path))));
+        }
+    }
+
+    Ok((files, None))
+}
+
+/// Undoes git's C-style quoting of a path with unusual characters.
+fn unquote(path: &str) -> String {
+    let Some(inner) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return path.to_string();
+    };
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.bytes().peekable();
+    while let Some(b) = chars.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'a') => bytes.push(0x07),
+            Some(b'b') => bytes.push(0x08),
+            Some(b'f') => bytes.push(0x0c),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b'v') => bytes.push(0x0b),
+            Some(d @ b'0'..=b'7') => {
+                let mut value = u32::from(d - b'0');
+                for _ in 0..2 {
+                    if let Some(&d @ b'0'..=b'7') = chars.peek() {
+                        value = value * 8 + u32::from(d - b'0');
+                        chars.next();
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            Some(other) => bytes.push(other),
+            None => {}
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// A path from a `---`/`+++` line, without its `a/`/`b/` prefix; `None`
+/// for `/dev/null`.
+fn side_path(raw: &str) -> Option<String> {
+    let path = unquote(raw.trim_end_matches('\t'));
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.get(2..).unwrap_or_default().to_string())
+}
+
+/// The path in a `diff --git a/<path> b/<path>` header. Only used when
+/// nothing else names the file (binary or mode-only changes), where both
+/// sides are the same path.
+fn header_path(rest: &str) -> String {
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let end = quoted.find("\" ").map_or(rest.len(), |i| i + 2);
+        return unquote(&rest[..end])
+            .get(2..)
+            .unwrap_or_default()
+            .to_string();
+    }
+    let half = rest.len().saturating_sub(1) / 2;
+    rest.get(2..half).unwrap_or_default().to_string()
+}
+
+/// Parses `git diff -p` output into files, the way libgit2 reports them.
+fn parse_patch(out: &str, source: DiffSource) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut in_hunk = false;
+    let (mut old_line, mut new_line) = (0, 0);
+
+    for line in out.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            in_hunk = false;
+            files.push(FileDiff {
+                path: header_path(rest),
+                old_path: None,
+                source,
+                status: "modified",
+                binary: false,
+                old_mode: None,
+                new_mode: None,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        if let Some(rest) = line
+            .strip_prefix("diff --cc ")
+            .or_else(|| line.strip_prefix("diff --combined "))
+        {
+            // Unmerged: the combined diff has no per-side lines to show.
+            in_hunk = false;
+            files.push(FileDiff {
+                path: unquote(rest),
+                old_path: None,
+                source,
+                status: "conflicted",
+                binary: false,
+                old_mode: None,
+                new_mode: None,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if file.status == "conflicted" {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("@@ -") {
+            let mut ranges = header.split(' ');
+            let range = |r: Option<&str>| -> (u32, u32) {
+                let r = r.unwrap_or_default().trim_start_matches(['-', '+']);
+                let (start, count) = r.split_once(',').unwrap_or((r, "1"));
+                (start.parse().unwrap_or(0), count.parse().unwrap_or(1))
+            };
+            let (old_start, old_lines) = range(ranges.next());
+            let (new_start, new_lines) = range(ranges.next());
+            (old_line, new_line) = (old_start, new_start);
+            in_hunk = true;
+            file.hunks.push(DiffHunkContent {
+                header: line.trim_end_matches('\r').to_string(),
+                old_start,
+                old_lines,
+                new_start,
+                new_lines,
+                lines: Vec::new(),
+            });
+            continue;
+        }
+        if in_hunk && let Some(hunk) = file.hunks.last_mut() {
+            let mut chars = line.chars();
+            let (origin, old_lineno, new_lineno) = match chars.next() {
+                Some(' ') => {
+                    old_line += 1;
+                    new_line += 1;
+                    (' ', Some(old_line - 1), Some(new_line - 1))
+                }
+                Some('-') => {
+                    old_line += 1;
+                    ('-', Some(old_line - 1), None)
+                }
+                Some('+') => {
+                    new_line += 1;
+                    ('+', None, Some(new_line - 1))
+                }
+                Some('\\') => {
+                    let origin = match hunk.lines.last().map(|l| l.origin) {
+                        Some('+') => '>',
+                        Some('-') => '<',
+                        _ => '=',
+                    };
+                    hunk.lines.push(DiffLineContent {
+                        origin,
+                        content: format!("\n{line}"),
+                        old_lineno: None,
+                        new_lineno: None,
+                    });
+                    continue;
+                }
+                _ => continue,
+            };
+            hunk.lines.push(DiffLineContent {
+                origin,
+                content: chars.as_str().trim_end_matches('\r').to_string(),
+                old_lineno,
+                new_lineno,
+            });
+            continue;
+        }
+
+        let mode = |m: &str| Some(m.trim().to_string());
+        if let Some(m) = line.strip_prefix("new file mode ") {
+            file.status = "added";
+            file.new_mode = mode(m);
+        } else if let Some(m) = line.strip_prefix("deleted file mode ") {
+            file.status = "deleted";
+            file.old_mode = mode(m);
+        } else if let Some(m) = line.strip_prefix("old mode ") {
+            file.old_mode = mode(m);
+        } else if let Some(m) = line.strip_prefix("new mode ") {
+            file.new_mode = mode(m);
+        } else if let Some(index) = line.strip_prefix("index ") {
+            if let Some((_, m)) = index.split_once(' ') {
+                file.old_mode = mode(m);
+                file.new_mode = mode(m);
+            }
+        } else if let Some(p) = line.strip_prefix("rename from ") {
+            file.status = "renamed";
+            file.old_path = Some(unquote(p));
+        } else if let Some(p) = line.strip_prefix("copy from ") {
+            file.status = "copied";
+            file.old_path = Some(unquote(p));
+        } else if let Some(p) = line
+            .strip_prefix("rename to ")
+            .or_else(|| line.strip_prefix("copy to "))
+        {
+            file.path = unquote(p);
+        } else if let Some(p) = line.strip_prefix("--- ") {
+            if let Some(p) = side_path(p) {
+                file.path = p;
+            }
+        } else if let Some(p) = line.strip_prefix("+++ ") {
+            if let Some(p) = side_path(p) {
+                file.path = p;
+            }
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        }
+    }
+
+    for file in files.iter_mut() {
+        // libgit2 gives files past `max_size` no hunks either.
+        let size: usize = file
+            .hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .map(|l| l.content.len() + 1)
+            .sum();
+        if size as i64 > MAX_DIFF_FILE_BYTES {
+            file.binary = true;
+            file.hunks.clear();
+        }
+        if file.old_path.as_ref() == Some(&file.path) {
+            file.old_path = None;
+        }
+    }
+    files
+}
+
+/// An untracked file as an all-added patch, read straight from disk.
+fn untracked_file(root: &Path, path: String) -> FileDiff {
+    let full = root.join(&path);
+    let meta = std::fs::symlink_metadata(&full).ok();
+    let is_link = meta.as_ref().is_some_and(|m| m.file_type().is_symlink());
+    let new_mode = meta.as_ref().map(|m| {
+        if is_link {
+            "120000".to_string()
+        } else if m.permissions().mode() & 0o111 != 0 {
+            "100755".to_string()
+        } else {
+            "100644".to_string()
+        }
+    });
+    let mut file = FileDiff {
+        path,
+        old_path: None,
+        source: DiffSource::Untracked,
+        status: "untracked",
+        binary: false,
+        old_mode: None,
+        new_mode,
+        hunks: Vec::new(),
+    };
+
+    let too_large = meta
+        .as_ref()
+        .is_some_and(|m| m.len() as i64 > MAX_DIFF_FILE_BYTES);
+    let content = if is_link {
+        std::fs::read_link(&full)
+            .map(|t| t.to_string_lossy().into_owned().into_bytes())
+            .unwrap_or_default()
+    } else if too_large {
+        file.binary = true;
+        return file;
+    } else {
+        std::fs::read(&full).unwrap_or_default()
+    };
+    // libgit2's binary check: a NUL near the start.
+    if content[..content.len().min(8000)].contains(&0) {
+        file.binary = true;
+        return file;
+    }
+    if content.is_empty() {
+        return file;
+    }
+
+    let mut lines: Vec<DiffLineContent> = content
+        .split_inclusive(|b| *b == b'\n')
+        .enumerate()
+        .map(|(i, line)| DiffLineContent {
+            origin: '+',
+            content: lossy(line),
+            old_lineno: None,
+            new_lineno: Some(i as u32 + 1),
+        })
+        .collect();
+    let count = lines.len() as u32;
+    // Reported the way libgit2 does for untracked content.
+    if !content.ends_with(b"\n") {
+        lines.push(DiffLineContent {
+            origin: '<',
+            content: "\n\\ No newline at end of file".to_string(),
+            old_lineno: None,
+            new_lineno: Some(count),
+        });
+    }
+    let header = if count == 1 {
+        "@@ -0,0 +1 @@".to_string()
+    } else {
+        format!("@@ -0,0 +1,{count} @@")
+    };
+    file.hunks.push(DiffHunkContent {
+        header,
+        old_start: 0,
+        old_lines: 0,
+        new_start: 1,
+        new_lines: count,
+        lines,
+    });
+    file
+}
+
+/// The same files through the git CLI, for repos libgit2 can't read
+/// (partial and sparse clones, sha256).
+async fn collect_cli(
+    path: &str,
+    body: &DiffContentBody,
+) -> Result<(Vec<GitFilePatch>, Option<String>), String> {
+    let pathspec: String = body
+        .paths
+        .iter()
+        .map(|p| format!(" {}", shell_quote(p)))
+        .collect();
+    let diff = |args: &str| {
+        format!(
+            "git -C '{path}' -c core.quotePath=false diff --no-color --no-ext-diff \
+             --no-textconv --src-prefix=a/ --dst-prefix=b/ -U{} {args} --{pathspec}",
+            body.context_lines.unwrap_or(3)
+        )
+    };
+
+    let mut parsed: Vec<FileDiff> = Vec::new();
+    let mut run = async |cmd: String, source: DiffSource| -> Result<(), String> {
+        let (code, out, stderr) = run_as_dev(&cmd).await;
+        if code != 0 {
+            return Err(stderr.trim().to_string());
+        }
+        parsed.extend(parse_patch(&out, source));
+        Ok(())
+    };
+    if body.staged {
+        run(diff("--cached -M"), DiffSource::Staged).await?;
+    }
+    if body.unstaged {
+        run(diff("--no-renames"), DiffSource::Unstaged).await?;
+    }
+    if body.untracked {
+        let (code, out, stderr) = run_as_dev(&format!(
+            "git -C '{path}' ls-files --others --exclude-standard -z --{pathspec}"
+        ))
+        .await;
+        if code != 0 {
+            return Err(stderr.trim().to_string());
+        }
+        let root = Path::new(path);
+        parsed.extend(
+            out.split('\0')
+                // Nested repos are listed as `dir/`.
+                .filter(|f| !f.is_empty() && !f.ends_with('/'))
+                .map(|f| untracked_file(root, f.to_string())),
+        );
+    }
+
+    let entries = parsed
+        .into_iter()
+        .map(|f| (f.source, f.path.clone(), PendingFile::Parsed(f)))
+        .collect();
+    page(entries, body).map_err(|e| e.message().to_string())
+}
+
+/// `None` when libgit2 can't read the repo.
+fn diff_content_native(body: &DiffContentBody) -> Option<GitDiffContentResponse> {
+    let repo = native::open(&full_path(&body.repo_path))?;
+    let mut resp = GitDiffContentResponse {
+        path: body.repo_path.clone(),
+        files: Vec::new(),
+        next_cursor: None,
+        error: None,
+    };
+    match collect_native(&repo, body) {
+        Ok((files, next_cursor)) => {
+            resp.files = files;
+            resp.next_cursor = next_cursor;
+        }
+        Err(e) => resp.error = Some(e.message().to_string()),
+    }
+    Some(resp)
+}
+
+async fn diff_content(body: DiffContentBody) -> GitDiffContentResponse {
+    let body = Arc::new(body);
+    let native_body = body.clone();
+    if let Ok(Some(resp)) =
+        tokio::task::spawn_blocking(move || diff_content_native(&native_body)).await
+    {
+        return resp;
+    }
+
+    let mut resp = GitDiffContentResponse {
+        path: body.repo_path.clone(),
+        files: Vec::new(),
+        next_cursor: None,
+        error: None,
+    };
+    match collect_cli(&full_path(&body.repo_path), &body).await {
+        Ok((files, next_cursor)) => {
+            resp.files = files;
+            resp.next_cursor = next_cursor;
+        }
+        Err(e) => resp.error = Some(e),
+    }
+    resp
+}
+
+pub async fn handle_git_diff_content(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: DiffContentBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => return json_ok(serde_json::json!({"path": "", "files": [], "error": e})),
+    };
+
+    let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+    json_ok(serde_json::to_value(diff_content(body).await).unwrap())
+}
@@ -0,0 +1,196 @@
+// Git LFS awareness. Git itself only stores a small pointer file for an
+// LFS-tracked path ("version ...\noid sha256:<hash>\nsize <bytes>\n"), so a
+// plain diff shows a couple of changed pointer lines. These helpers spot
+// LFS paths by their `filter=lfs` attribute and read the pointers on each
+// side instead.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::{GitDiffFile, run_as_dev_stdin};
+
+// Pointer files are ~130 bytes; anything much bigger is real content.
+const MAX_POINTER_BYTES: usize = 1024;
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/";
+
+/// Old side is HEAD, new side the working tree. When git-lfs has checked the
+/// content out, the working tree holds the real file and only its size is
+/// known; the oid would mean hashing the whole file.
+#[derive(Clone, Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GitLfsChange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_oid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_oid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_size: Option<u64>,
+}
+
+/// Parses an LFS pointer into `(oid, size)`.
+fn parse_pointer(text: &str) -> Option<(String, u64)> {
+    if !text.starts_with(POINTER_VERSION) {
+        return None;
+    }
+    let mut oid = None;
+    let mut size = None;
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("oid ") {
+            oid = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("size ") {
+            size = value.parse().ok();
+        }
+    }
+    Some((oid?, size?))
+}
+
+/// Paths among `files` whose `filter` attribute is `lfs`.
+pub(super) async fn lfs_paths(path: &str, files: &[&str]) -> HashSet<String> {
+    if files.is_empty() {
+        return HashSet::new();
+    }
+    let (code, out, _) = run_as_dev_stdin(
+        &format!("git -C '{path}' check-attr -z --stdin filter"),
+        nul_separated(files.iter().copied()),
+    )
+    .await;
+    if code != 0 {
+        return HashSet::new();
+    }
+    // -z output is `<path> NUL <attribute> NUL <value> NUL` per path.
+    let fields: Vec<&str> = out.split('\0').collect();
+    fields
+        .chunks_exact(3)
+        .filter(|c| c[2] == "lfs")
+        .map(|c| c[0].to_string())
+        .collect()
+}
+
+/// The working tree side: a pointer when the content was never smudged
+/// (git-lfs missing, or `GIT_LFS_SKIP_SMUDGE`), the real file otherwise.
+fn worktree_side(file: &Path) -> (Option<String>, Option<u64>) {
+    let Ok(meta) = std::fs::metadata(file) else {
+        return (None, None);
+    };
+    if meta.len() <= MAX_POINTER_BYTES as u64 {
+        let mut buf = Vec::new();
+        if std::fs::File::open(file)
+            .and_then(|f| f.take(MAX_POINTER_BYTES as u64).read_to_end(&mut buf))
+            .is_ok()
+            && let Some((oid, size)) = parse_pointer(&String::from_utf8_lossy(&buf))
+        {
+            return (Some(oid), Some(size));
+        }
+    }
+    (None, Some(meta.len()))
+}
+
+/// `items` as NUL-terminated entries, for a `-z` command's stdin.
+fn nul_separated<'a>(items: impl Iterator<Item = &'a str>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for item in items {
+        out.extend_from_slice(item.as_bytes());
+        out.push(0);
+    }
+    out
+}
+
+/// The HEAD side of each of `files` that is a pointer, keyed by path.
+/// Two `cat-file` batches cover every file: the first looks up sizes, so
+/// the second only reads blobs small enough to be pointers.
+async fn head_pointers(path: &str, files: &[&str]) -> HashMap<String, (String, u64)> {
+    let specs: Vec<String> = files.iter().map(|f| format!("HEAD:{f}")).collect();
+    let (code, out, _) = run_as_dev_stdin(
+        &format!(
+            "git -C '{path}' cat-file -z --batch-check='%(objectname) %(objecttype) %(objectsize)'"
+        ),
+        nul_separated(specs.iter().map(String::as_str)),
+    )
+    .await;
+    // One line per input, in order; missing objects print `<spec> missing`.
+    let lines: Vec<&str> = out.lines().collect();
+    if code != 0 || lines.len() != files.len() {
+        return HashMap::new();
+    }
+    let mut candidates: Vec<(&str, &str)> = Vec::new();
+    for (file, line) in files.iter().zip(lines) {
+        let mut fields = line.split(' ');
+        if let (Some(oid), Some("blob"), Some(size)) = (fields.next(), fields.next(), fields.next())
+            && size.parse().is_ok_and(|s: usize| s <= MAX_POINTER_BYTES)
+        {
+            candidates.push((file, oid));
+        }
+    }
+    if candidates.is_empty() {
+        return HashMap::new();
+    }
+
+    // `tr` keeps byte counts intact while making any binary content valid
+    // text, so the sizes in the headers still line up.
+    let oids: String = candidates
+        .iter()
+        .map(|(_, oid)| format!("{oid}\n"))
+        .collect();
+    let (code, out, _) = run_as_dev_stdin(
+        &format!(
+            "set -o pipefail && git -C '{path}' cat-file --batch \
+             | LC_ALL=C tr -c '\\n[:print:]' '?'"
+        ),
+        oids.into_bytes(),
+    )
+    .await;
+    if code != 0 {
+        return HashMap::new();
+    }
+    // `<oid> blob <size>\n<content>\n` per object.
+    let mut blobs: HashMap<&str, &str> = HashMap::new();
+    let mut rest = out.as_str();
+    while let Some((header, body)) = rest.split_once('\n') {
+        let mut fields = header.split(' ');
+        let (Some(oid), Some(size)) = (fields.next(), fields.nth(1)) else {
+            break;
+        };
+        let Some(content) = size.parse().ok().and_then(|size: usize| body.get(..size)) else {
+            break;
+        };
+        blobs.insert(oid, content);
+        rest = body.get(content.len() + 1..).unwrap_or("");
+    }
+    candidates
+        .into_iter()
+        .filter_map(|(file, oid)| {
+            let pointer = parse_pointer(blobs.get(oid)?)?;
+            Some((file.to_string(), pointer))
+        })
+        .collect()
+}
+
+/// Fills in `lfs` on the LFS-tracked entries of a diff.
+pub(super) async fn annotate_diff(path: &str, files: &mut [GitDiffFile]) {
+    let names: Vec<&str> = files
+        .iter()
+        .filter(|f| !f.submodule)
+        .map(|f| f.path.as_str())
+        .collect();
+    let tracked = lfs_paths(path, &names).await;
+    if tracked.is_empty() {
+        return;
+    }
+    let tracked_names: Vec<&str> = tracked.iter().map(String::as_str).collect();
+    let mut old = head_pointers(path, &tracked_names).await;
+    for file in files.iter_mut().filter(|f| tracked.contains(&f.path)) {
+        let (old_oid, old_size) = old.remove(&file.path).unzip();
+        let (new_oid, new_size) = worktree_side(&Path::new(path).join(&file.path));
+        file.lfs = Some(GitLfsChange {
+            old_oid,
+            old_size,
+            new_oid,
+            new_size,
+        });
+    }
+}
@@ -0,0 +1,184 @@
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{default_true, full_path, read_json_body, run_as_dev, shell_quote};
+use crate::limits::GIT_SEMAPHORE;
+use crate::response::json_ok;
+
+const DEFAULT_LOG_LIMIT: usize = 50;
+const MAX_LOG_LIMIT: usize = 500;
+
+// Record separator ahead of each commit; the message itself may contain
+// newlines, so lines can't delimit commits.
+const RECORD_SEP: char = '\x1e';
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LogBody {
+    repo_path: String,
+    /// A ref (`main`, `v1.2`) or range (`origin/main..HEAD`); HEAD by default.
+    #[serde(default, rename = "ref")]
+    rev: Option<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default = "default_true")]
+    stats: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitSignature {
+    name: String,
+    email: String,
+    date: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitCommitFileStat {
+    path: String,
+    added: u32,
+    removed: u32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    binary: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitLogCommit {
+    hash: String,
+    short_hash: String,
+    parents: Vec<String>,
+    author: GitSignature,
+    committer: GitSignature,
+    subject: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<GitCommitFileStat>>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitLogResponse {
+    path: String,
+    commits: Vec<GitLogCommit>,
+    has_more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn parse_numstat(lines: &str) -> Vec<GitCommitFileStat> {
+    lines
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let (added, removed, path) = (parts.next()?, parts.next()?, parts.next()?);
+            Some(GitCommitFileStat {
+                path: path.to_string(),
+                added: added.parse().unwrap_or(0),
+                removed: removed.parse().unwrap_or(0),
+                binary: added == "-",
+            })
+        })
+        .collect()
+}
+
+fn parse_record(record: &str, stats: bool) -> Option<GitLogCommit> {
+    let mut fields = record.splitn(11, '\0');
+    let mut next = || fields.next().map(str::to_string);
+    let hash = next()?;
+    let short_hash = next()?;
+    let parents = next()?;
+    let author = GitSignature {
+        name: next()?,
+        email: next()?,
+        date: next()?,
+    };
+    let committer = GitSignature {
+        name: next()?,
+        email: next()?,
+        date: next()?,
+    };
+    let message = next()?.trim_end().to_string();
+    let numstat = next()?;
+
+    Some(GitLogCommit {
+        hash,
+        short_hash,
+        parents: parents.split_whitespace().map(str::to_string).collect(),
+        author,
+        committer,
+        subject: message.lines().next().unwrap_or_default().to_string(),
+        message,
+        files: stats.then(|| parse_numstat(&numstat)),
+    })
+}
+
+async fn git_log(body: LogBody) -> GitLogResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitLogResponse {
+        path: body.repo_path,
+        ..Default::default()
+    };
+
+    let rev = body.rev.as_deref().unwrap_or("HEAD");
+    if rev.starts_with('-') {
+        resp.error = Some(format!("Invalid ref: {rev}"));
+        return resp;
+    }
+    let limit = body
+        .limit
+        .unwrap_or(DEFAULT_LOG_LIMIT)
+        .clamp(1, MAX_LOG_LIMIT);
+    let stats = if body.stats { " --numstat" } else { "" };
+    let paths: String = body
+        .paths
+        .iter()
+        .map(|p| format!(" {}", shell_quote(p)))
+        .collect();
+
+    // One extra commit tells whether another page exists.
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' log --format='%x1e%H%x00%h%x00%P%x00%an%x00%ae%x00%aI%x00%cn%x00%ce%x00%cI%x00%B%x00'{stats} --skip={} --max-count={} {} --{paths}",
+        body.offset,
+        limit + 1,
+        shell_quote(rev)
+    ))
+    .await;
+    if code != 0 {
+        resp.error = Some(stderr.trim().to_string());
+        return resp;
+    }
+
+    resp.commits = out
+        .split(RECORD_SEP)
+        .filter_map(|r| parse_record(r, body.stats))
+        .collect();
+    if resp.commits.len() > limit {
+        resp.commits.truncate(limit);
+        resp.has_more = true;
+        resp.next_offset = Some(body.offset + limit);
+    }
+    resp
+}
+
+pub async fn handle_git_log(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: LogBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => {
+            return json_ok(serde_json::json!({
+                "path": "", "commits": [], "hasMore": false, "error": e
+            }));
+        }
+    };
+    let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+    json_ok(serde_json::to_value(git_log(body).await).unwrap())
+}
@@ -0,0 +1,626 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::body::{read_body_limited, ReadBodyError};
+use crate::command::run_shell_command_with_input;
+use crate::config::{get_config, DEFAULT_EXEC_TIMEOUT_MS};
+use crate::limits::{GIT_SEMAPHORE, MAX_COMMAND_OUTPUT_BYTES, MAX_REQUEST_BODY_BYTES};
+use crate::response::json_ok;
+
+mod blame;
+mod branches;
+mod checkpoint;
+mod checks;
+mod clone;
+mod commit;
+mod compare;
+mod conflicts;
+mod diff_content;
+mod lfs;
+mod log;
+mod native;
+mod pull;
+mod push;
+mod stash;
+mod status_cache;
+mod status_detail;
+mod submodules;
+mod worktrees;
+
+pub use blame::handle_git_blame;
+pub use branches::{
+    handle_git_branch_checkout, handle_git_branch_create, handle_git_branch_delete,
+    handle_git_branch_rename, handle_git_branches,
+};
+pub use checkpoint::{
+    handle_git_checkpoint, handle_git_checkpoint_diff, handle_git_checkpoint_list,
+    handle_git_checkpoint_prune, handle_git_checkpoint_restore,
+};
+pub use checks::handle_git_checks;
+pub use clone::{handle_git_clone, handle_git_clone_status};
+pub use commit::handle_git_commit;
+pub use compare::handle_git_compare;
+pub use conflicts::{
+    handle_git_conflicts, handle_git_conflicts_abort, handle_git_conflicts_continue,
+    handle_git_conflicts_resolve,
+};
+pub use diff_content::handle_git_diff_content;
+pub use log::handle_git_log;
+pub use pull::{handle_git_fetch, handle_git_pull};
+pub use push::handle_git_push;
+pub use status_cache::{
+    handle_git_events, invalidate as invalidate_status, start as start_status_cache,
+};
+pub use stash::{
+    handle_git_stash_apply, handle_git_stash_drop, handle_git_stash_list, handle_git_stash_pop,
+    handle_git_stash_push, handle_git_stash_show,
+};
+pub use worktrees::{
+    handle_git_worktree_add, handle_git_worktree_prune, handle_git_worktree_remove,
+    handle_git_worktrees,
+};
+
+// Bounds for repo discovery under /home/dev: `/workspace/org/repo` is 3.
+const DISCOVERY_MAX_DEPTH: usize = 4;
+const DISCOVERY_MAX_DIRS: usize = 5_000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepoRef {
+    clone_path: String,
+}
+
+/// Without `repos`, the routes use the sandbox's configured repos; with
+/// `discover`, repos found under `/home/dev` are added.
+#[derive(Deserialize, Default)]
+struct MultiRepoBody {
+    #[serde(default)]
+    repos: Vec<RepoRef>,
+    #[serde(default)]
+    detailed: bool,
+    #[serde(default)]
+    discover: bool,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitRepoStatus {
+    path: String,
+    branch: Option<String>,
+    dirty: bool,
+    ahead: u32,
+    behind: u32,
+    last_commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<status_detail::GitFileStatus>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stash_count: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    submodules: Vec<submodules::GitSubmoduleStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct GitStatusResponse {
+    repos: Vec<GitRepoStatus>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitDiffFile {
+    path: String,
+    added: u32,
+    removed: u32,
+    /// A gitlink; its changes are under the repo's `submodules`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    submodule: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lfs: Option<lfs::GitLfsChange>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitDiffRepo {
+    path: String,
+    files: Vec<GitDiffFile>,
+    total_added: u32,
+    total_removed: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    submodules: Vec<GitDiffRepo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct GitDiffResponse {
+    repos: Vec<GitDiffRepo>,
+}
+
+fn parse_exec_value(v: serde_json::Value) -> (i32, String, String) {
+    let exit_code = v.get("exitCode").and_then(|x| x.as_i64()).unwrap_or(1) as i32;
+    let stdout = v
+        .get("stdout")
+        .and_then(|x| x.as_str())
+        .unwrap_or("")
+        .to_string();
+    let stderr = v
+        .get("stderr")
+        .and_then(|x| x.as_str())
+        .unwrap_or("")
+        .to_string();
+    (exit_code, stdout, stderr)
+}
+
+async fn run_as_dev(cmd: &str) -> (i32, String, String) {
+    run_as_dev_timeout(cmd, DEFAULT_EXEC_TIMEOUT_MS).await
+}
+
+async fn run_as_dev_timeout(cmd: &str, timeout_ms: u64) -> (i32, String, String) {
+    run_dev_command(cmd, None, timeout_ms).await
+}
+
+/// `run_as_dev` with `input` on the command's stdin, for path lists that
+/// could overflow the argument list.
+async fn run_as_dev_stdin(cmd: &str, input: Vec<u8>) -> (i32, String, String) {
+    run_dev_command(cmd, Some(input), DEFAULT_EXEC_TIMEOUT_MS).await
+}
+
+async fn run_dev_command(
+    cmd: &str,
+    input: Option<Vec<u8>>,
+    timeout_ms: u64,
+) -> (i32, String, String) {
+    let env: String = crate::git_credential::helper_env()
+        .into_iter()
+        .map(|(key, value)| format!("export {key}={}; ", shell_quote(&value)))
+        .collect();
+    let v = run_shell_command_with_input(
+        &format!("{env}{cmd}"),
+        input,
+        timeout_ms,
+        Some("dev"),
+        None,
+        MAX_COMMAND_OUTPUT_BYTES,
+    )
+    .await;
+    parse_exec_value(v)
+}
+
+/// Names the merge/rebase/cherry-pick/revert the repo is stopped in, if any.
+async fn operation_in_progress(path: &str) -> Option<&'static str> {
+    let (code, git_dir, _) =
+        run_as_dev(&format!("git -C '{path}' rev-parse --absolute-git-dir")).await;
+    if code != 0 {
+        return None;
+    }
+    let git_dir = std::path::Path::new(git_dir.trim());
+    [
+        ("rebase-merge", "rebase"),
+        ("rebase-apply", "rebase"),
+        ("MERGE_HEAD", "merge"),
+        ("CHERRY_PICK_HEAD", "cherry-pick"),
+        ("REVERT_HEAD", "revert"),
+    ]
+    .into_iter()
+    .find(|(marker, _)| git_dir.join(marker).exists())
+    .map(|(_, op)| op)
+}
+
+async fn conflicted_files(path: &str) -> Vec<String> {
+    let (_, out, _) =
+        run_as_dev(&format!("git -C '{path}' diff --name-only --diff-filter=U -z")).await;
+    out.split('\0')
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+async fn read_json_body<T: DeserializeOwned>(
+    req: Request<hyper::body::Incoming>,
+) -> Result<T, String> {
+    let body = read_body_limited(req, MAX_REQUEST_BODY_BYTES)
+        .await
+        .map_err(|e| match e {
+            ReadBodyError::TooLarge => "Request body too large".to_string(),
+            ReadBodyError::ReadFailed => "Failed to read body".to_string(),
+        })?;
+    serde_json::from_slice(&body).map_err(|_| "Invalid JSON".to_string())
+}
+
+/// Parses the request's JSON body and runs `action` on it under
+/// `GIT_SEMAPHORE`. A body that can't be read is answered with
+/// `failed(path, error)`, which matches the responses' `failed`
+/// constructors; the path is empty since the body never said.
+async fn handle_json_request<T, R, F>(
+    req: Request<hyper::body::Incoming>,
+    failed: impl FnOnce(String, String) -> R,
+    action: impl FnOnce(T) -> F,
+) -> Response<Full<Bytes>>
+where
+    T: DeserializeOwned,
+    R: Serialize,
+    F: Future<Output = R>,
+{
+    handle_json_body(req, failed, |body| async move {
+        let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+        action(body).await
+    })
+    .await
+}
+
+/// `handle_json_request` for actions that take their own permits, such as
+/// ones that fetch before doing local work.
+async fn handle_json_body<T, R, F>(
+    req: Request<hyper::body::Incoming>,
+    failed: impl FnOnce(String, String) -> R,
+    action: impl FnOnce(T) -> F,
+) -> Response<Full<Bytes>>
+where
+    T: DeserializeOwned,
+    R: Serialize,
+    F: Future<Output = R>,
+{
+    let body: T = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => return json_ok(serde_json::to_value(failed(String::new(), e)).unwrap()),
+    };
+    json_ok(serde_json::to_value(action(body).await).unwrap())
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Quotes an argument for the `bash -c` line `run_as_dev` builds.
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+pub fn full_path(clone_path: &str) -> String {
+    format!("/home/dev{clone_path}")
+}
+
+/// Repos under `/home/dev` as clone paths, up to `DISCOVERY_MAX_DEPTH`
+/// levels down. Hidden directories and `node_modules` are skipped, and the
+/// walk doesn't descend into repos it finds.
+fn discover_repos() -> Vec<String> {
+    fn walk(dir: &std::path::Path, depth: usize, found: &mut Vec<String>, visited: &mut usize) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if *visited >= DISCOVERY_MAX_DIRS {
+                return;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name == "node_modules" {
+                continue;
+            }
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            *visited += 1;
+            let path = entry.path();
+            if path.join(".git").exists() {
+                if let Ok(rel) = path.strip_prefix("/home/dev") {
+                    found.push(format!("/{}", rel.to_string_lossy()));
+                }
+            } else if depth < DISCOVERY_MAX_DEPTH {
+                walk(&path, depth + 1, found, visited);
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    walk(std::path::Path::new("/home/dev"), 1, &mut found, &mut 0);
+    found.sort();
+    found
+}
+
+/// Clone paths a multi-repo request applies to.
+async fn target_repos(body: &MultiRepoBody) -> Vec<String> {
+    let mut paths: Vec<String> = if body.repos.is_empty() {
+        get_config()
+            .map(|c| c.repos.into_iter().map(|r| r.clone_path).collect())
+            .unwrap_or_default()
+    } else {
+        body.repos.iter().map(|r| r.clone_path.clone()).collect()
+    };
+    if body.discover {
+        let discovered = tokio::task::spawn_blocking(discover_repos)
+            .await
+            .unwrap_or_default();
+        for path in discovered {
+            if !paths
+                .iter()
+                .any(|p| p.trim_end_matches('/') == path.trim_end_matches('/'))
+            {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+fn parse_multi_repo_body(body: &[u8]) -> Option<MultiRepoBody> {
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Some(MultiRepoBody::default());
+    }
+    serde_json::from_slice(body).ok()
+}
+
+async fn get_repo_status(clone_path: String) -> GitRepoStatus {
+    let path = full_path(&clone_path);
+    let native_clone_path = clone_path.clone();
+    match tokio::task::spawn_blocking(move || native::repo_status(&path, native_clone_path)).await
+    {
+        Ok(Some(status)) => status,
+        _ => get_repo_status_cli(clone_path).await,
+    }
+}
+
+/// Status of a repo including its submodules, each reported as a nested
+/// repo. Boxed because submodules recurse back into it.
+fn repo_status(
+    clone_path: String,
+    detailed: bool,
+) -> Pin<Box<dyn Future<Output = GitRepoStatus> + Send>> {
+    Box::pin(async move {
+        let mut status = if detailed {
+            status_detail::get_repo_status_detailed(clone_path).await
+        } else {
+            get_repo_status(clone_path).await
+        };
+        if status.error.is_some() {
+            return status;
+        }
+        let path = full_path(&status.path);
+        status.submodules = submodules::submodule_statuses(&path, &status.path, detailed).await;
+        status.dirty |= status
+            .submodules
+            .iter()
+            .any(|s| s.status.dirty || s.out_of_sync);
+        status
+    })
+}
+
+async fn get_repo_status_cli(clone_path: String) -> GitRepoStatus {
+    let path = full_path(&clone_path);
+
+    let (code, _, _) = run_as_dev(&format!("git -C '{path}' rev-parse --git-dir")).await;
+    if code != 0 {
+        return GitRepoStatus {
+            path: clone_path,
+            branch: None,
+            dirty: false,
+            ahead: 0,
+            behind: 0,
+            last_commit: None,
+            files: None,
+            stash_count: None,
+            submodules: Vec::new(),
+            error: Some("Not a git repository".to_string()),
+        };
+    }
+
+    let (_, branch_out, _) = run_as_dev(&format!("git -C '{path}' branch --show-current")).await;
+    let branch = branch_out.trim().to_string();
+
+    // No index refresh: the write would wake the status watcher for nothing.
+    let (_, dirty_out, _) = run_as_dev(&format!(
+        "git -C '{path}' --no-optional-locks status --porcelain | head -1"
+    ))
+    .await;
+    let dirty = !dirty_out.trim().is_empty();
+
+    let (ab_code, ab_out, _) = run_as_dev(&format!(
+        "git -C '{path}' rev-list --left-right --count HEAD...@{{upstream}}"
+    ))
+    .await;
+    let (ahead, behind) = if ab_code == 0 {
+        let parts: Vec<&str> = ab_out.split_whitespace().collect();
+        (
+            parts.first().and_then(|s| s.parse().ok()).unwrap_or(0u32),
+            parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(0u32),
+        )
+    } else {
+        (0, 0)
+    };
+
+    let (_, commit_out, _) = run_as_dev(&format!("git -C '{path}' log -1 --format='%h %s'")).await;
+    let commit = commit_out.trim().to_string();
+
+    GitRepoStatus {
+        path: clone_path,
+        branch: if branch.is_empty() {
+            None
+        } else {
+            Some(branch)
+        },
+        dirty,
+        ahead,
+        behind,
+        last_commit: if commit.is_empty() {
+            None
+        } else {
+            Some(commit)
+        },
+        files: None,
+        stash_count: None,
+        submodules: Vec::new(),
+        error: None,
+    }
+}
+
+pub async fn handle_git_status(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body = match read_body_limited(req, MAX_REQUEST_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(ReadBodyError::TooLarge) => {
+            return json_ok(serde_json::json!({
+                "repos": [],
+                "error": "Request body too large"
+            }))
+        }
+        Err(ReadBodyError::ReadFailed) => return json_ok(serde_json::json!({"repos": []})),
+    };
+    let Some(parsed) = parse_multi_repo_body(&body) else {
+        return json_ok(serde_json::json!({"repos": []}));
+    };
+
+    status_cache::ensure_watched().await;
+    let mut set = tokio::task::JoinSet::new();
+    for clone_path in target_repos(&parsed).await {
+        set.spawn(status_cache::cached_status(clone_path, parsed.detailed));
+    }
+
+    let mut repos = Vec::with_capacity(set.len());
+    while let Some(Ok(status)) = set.join_next().await {
+        repos.push(status);
+    }
+
+    json_ok(serde_json::to_value(GitStatusResponse { repos }).unwrap())
+}
+
+async fn get_repo_diff(clone_path: String) -> GitDiffRepo {
+    let path = full_path(&clone_path);
+    let native_clone_path = clone_path.clone();
+    match tokio::task::spawn_blocking(move || native::repo_diff(&path, native_clone_path)).await {
+        Ok(Some(diff)) => diff,
+        _ => get_repo_diff_cli(clone_path).await,
+    }
+}
+
+/// Diff of a repo with submodule gitlinks and LFS pointers marked, and each
+/// initialized submodule's own diff nested under it.
+fn repo_diff(clone_path: String) -> Pin<Box<dyn Future<Output = GitDiffRepo> + Send>> {
+    Box::pin(async move {
+        let mut diff = get_repo_diff(clone_path).await;
+        if diff.error.is_some() {
+            return diff;
+        }
+        let path = full_path(&diff.path);
+        let subs = submodules::submodules(&path).await;
+        for file in diff.files.iter_mut() {
+            file.submodule = subs.iter().any(|s| s.path == file.path);
+        }
+        lfs::annotate_diff(&path, &mut diff.files).await;
+        for sub in subs.iter().filter(|s| submodules::is_initialized(&path, s)) {
+            let nested = repo_diff(submodules::nested_clone_path(&diff.path, sub)).await;
+            diff.submodules.push(nested);
+        }
+        diff
+    })
+}
+
+async fn get_repo_diff_cli(clone_path: String) -> GitDiffRepo {
+    let path = full_path(&clone_path);
+
+    let (code, _, _) = run_as_dev(&format!("git -C '{path}' rev-parse --git-dir")).await;
+    if code != 0 {
+        return GitDiffRepo {
+            path: clone_path,
+            files: vec![],
+            total_added: 0,
+            total_removed: 0,
+            submodules: Vec::new(),
+            error: Some("Not a git repository".to_string()),
+        };
+    }
+
+    // Without --cached, `git diff HEAD` compares against the working tree, so
+    // a file with both staged and unstaged edits is counted once.
+    let (_, numstat_out, _) = run_as_dev(&format!(
+        "git -C '{path}' diff --numstat --no-renames HEAD"
+    ))
+    .await;
+    let (_, untracked_out, _) = run_as_dev(&format!(
+        "git -C '{path}' ls-files -z --others --exclude-standard"
+    ))
+    .await;
+
+    let mut files = Vec::new();
+    let mut total_added: u32 = 0;
+    let mut total_removed: u32 = 0;
+
+    for line in numstat_out.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = line.split('\t').collect();
+        if parts.len() >= 3 {
+            let added: u32 = parts[0].parse().unwrap_or(0);
+            let removed: u32 = parts[1].parse().unwrap_or(0);
+            let file_path = parts[2..].join("\t");
+            total_added += added;
+            total_removed += removed;
+            files.push(GitDiffFile {
+                path: file_path,
+                added,
+                removed,
+                ..Default::default()
+            });
+        }
+    }
+
+    for file in untracked_out.split('\0').filter(|f| !f.is_empty()) {
+        let (lines, _) = status_detail::count_lines(&std::path::Path::new(&path).join(file));
+        let added = lines.unwrap_or(0);
+        total_added += added;
+        files.push(GitDiffFile {
+            path: file.to_string(),
+            added,
+            removed: 0,
+            ..Default::default()
+        });
+    }
+
+    GitDiffRepo {
+        path: clone_path,
+        files,
+        total_added,
+        total_removed,
+        submodules: Vec::new(),
+        error: None,
+    }
+}
+
+pub async fn handle_git_diff(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body = match read_body_limited(req, MAX_REQUEST_BODY_BYTES).await {
+        Ok(b) => b,
+        Err(ReadBodyError::TooLarge) => {
+            return json_ok(serde_json::json!({
+                "repos": [],
+                "error": "Request body too large"
+            }))
+        }
+        Err(ReadBodyError::ReadFailed) => return json_ok(serde_json::json!({"repos": []})),
+    };
+    let Some(parsed) = parse_multi_repo_body(&body) else {
+        return json_ok(serde_json::json!({"repos": []}));
+    };
+
+    let mut set = tokio::task::JoinSet::new();
+    for clone_path in target_repos(&parsed).await {
+        set.spawn(async move {
+            let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+            repo_diff(clone_path).await
+        });
+    }
+
+    let mut repos = Vec::with_capacity(set.len());
+    while let Some(Ok(diff)) = set.join_next().await {
+        repos.push(diff);
+    }
+
+    json_ok(serde_json::to_value(GitDiffResponse { repos }).unwrap())
+}
+
@@ -0,0 +1,196 @@
+// In-process (libgit2) versions of the read-only git queries. Every function
+// returns `None` when the repo can't be handled natively and the caller falls
+// back to the `git` CLI, so output must stay identical to the CLI paths.
+
+use std::path::Path;
+use std::sync::Once;
+
+use git2::{ConfigLevel, Diff, Patch, Repository, Status, StatusOptions};
+
+use super::status_detail::count_lines;
+use super::{GitDiffFile, GitDiffRepo, GitRepoStatus};
+
+static INIT: Once = Once::new();
+
+fn init() {
+    INIT.call_once(|| {
+        // SAFETY: called once, before any other libgit2 use in this process.
+        unsafe {
+            // Repos are owned by `dev` while the agent runs as root; the CLI
+            // paths run git as `dev`, so the ownership check would only ever
+            // reject repos the CLI accepts.
+            let _ = git2::opts::set_verify_owner_validation(false);
+            // Pick up dev's ~/.gitconfig (core.excludesFile, status settings)
+            // rather than root's.
+            let _ = git2::opts::set_search_path(ConfigLevel::Global, "/home/dev");
+        }
+    });
+}
+
+/// Repo features libgit2 doesn't implement (or implements differently enough
+/// that status would be wrong). These go through the CLI.
+fn is_supported(repo: &Repository) -> bool {
+    let Ok(cfg) = repo.config() else {
+        return false;
+    };
+    let enabled = |key: &str| cfg.get_bool(key).unwrap_or(false);
+    let object_format = cfg.get_string("extensions.objectFormat").ok();
+
+    !(enabled("core.sparseCheckout")
+        || enabled("index.sparse")
+        || cfg.get_string("extensions.partialClone").is_ok()
+        || object_format.is_some_and(|f| !f.eq_ignore_ascii_case("sha1")))
+}
+
+pub(super) fn open(path: &str) -> Option<Repository> {
+    init();
+    let repo = Repository::discover(path).ok()?;
+    if repo.is_bare() || !is_supported(&repo) {
+        return None;
+    }
+    Some(repo)
+}
+
+fn current_branch(repo: &Repository) -> Option<String> {
+    // `git branch --show-current` also names an unborn branch, which
+    // `repo.head()` can't resolve, so read the symbolic ref directly.
+    let head = repo.find_reference("HEAD").ok()?;
+    let target = head.symbolic_target()?;
+    target.strip_prefix("refs/heads/").map(str::to_string)
+}
+
+fn is_dirty(repo: &Repository) -> Result<bool, git2::Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .include_ignored(false);
+    let statuses = repo.statuses(Some(&mut opts))?;
+    Ok(statuses.iter().any(|e| e.status() != Status::CURRENT))
+}
+
+fn ahead_behind(repo: &Repository, branch: &str) -> Option<(u32, u32)> {
+    let local = repo
+        .find_reference(&format!("refs/heads/{branch}"))
+        .ok()?
+        .target()?;
+    let upstream_name = repo
+        .branch_upstream_name(&format!("refs/heads/{branch}"))
+        .ok()?;
+    let upstream = repo
+        .find_reference(upstream_name.as_str()?)
+        .ok()?
+        .target()?;
+    let (ahead, behind) = repo.graph_ahead_behind(local, upstream).ok()?;
+    Some((ahead as u32, behind as u32))
+}
+
+fn last_commit(repo: &Repository) -> Option<String> {
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    let short = commit.as_object().short_id().ok()?;
+    Some(format!(
+        "{} {}",
+        short.as_str()?,
+        commit.summary().unwrap_or_default()
+    ))
+}
+
+pub(super) fn repo_status(path: &str, clone_path: String) -> Option<GitRepoStatus> {
+    let repo = open(path)?;
+    let branch = current_branch(&repo);
+    let dirty = is_dirty(&repo).ok()?;
+    let (ahead, behind) = branch
+        .as_deref()
+        .and_then(|b| ahead_behind(&repo, b))
+        .unwrap_or((0, 0));
+
+    Some(GitRepoStatus {
+        path: clone_path,
+        branch,
+        dirty,
+        ahead,
+        behind,
+        last_commit: last_commit(&repo),
+        files: None,
+        stash_count: None,
+        submodules: Vec::new(),
+        error: None,
+    })
+}
+
+// No rename detection, like the CLI's `--no-renames`: a rename counts as a
+// deletion plus an addition.
+fn push_numstat(diff: &Diff, files: &mut Vec<GitDiffFile>) -> Result<(), git2::Error> {
+    for idx in 0..diff.deltas().len() {
+        let Some(delta) = diff.get_delta(idx) else {
+            continue;
+        };
+        let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+            continue;
+        };
+        let (added, removed) = match Patch::from_diff(diff, idx)? {
+            // Binary files report `-` in numstat, which the CLI path parses as 0.
+            Some(patch) if !patch.delta().flags().is_binary() => {
+                let (_, added, removed) = patch.line_stats()?;
+                (added as u32, removed as u32)
+            }
+            _ => (0, 0),
+        };
+        files.push(GitDiffFile {
+            path: path.to_string_lossy().into_owned(),
+            added,
+            removed,
+            ..Default::default()
+        });
+    }
+    Ok(())
+}
+
+fn untracked_files(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo.statuses(Some(&mut opts))?;
+    Ok(statuses
+        .iter()
+        .filter(|e| e.status().contains(Status::WT_NEW))
+        .filter_map(|e| e.path().map(str::to_string))
+        .collect())
+}
+
+pub(super) fn repo_diff(path: &str, clone_path: String) -> Option<GitDiffRepo> {
+    let repo = open(path)?;
+    let mut files = Vec::new();
+
+    // Mirrors `git diff --numstat HEAD`, which covers staged and unstaged
+    // changes in one go; it fails (and contributes nothing) while HEAD is
+    // unborn.
+    if let Ok(tree) = repo.head().and_then(|h| h.peel_to_tree()) {
+        let diff = repo
+            .diff_tree_to_workdir_with_index(Some(&tree), None)
+            .ok()?;
+        push_numstat(&diff, &mut files).ok()?;
+    }
+
+    for untracked in untracked_files(&repo).ok()? {
+        let (lines, _) = count_lines(&Path::new(path).join(&untracked));
+        files.push(GitDiffFile {
+            path: untracked,
+            added: lines.unwrap_or(0),
+            removed: 0,
+            ..Default::default()
+        });
+    }
+
+    let total_added = files.iter().map(|f| f.added).sum();
+    let total_removed = files.iter().map(|f| f.removed).sum();
+
+    Some(GitDiffRepo {
+        path: clone_path,
+        files,
+        total_added,
+        total_removed,
+        submodules: Vec::new(),
+        error: None,
+    })
+}
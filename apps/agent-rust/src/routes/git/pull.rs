@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    conflicted_files, full_path, operation_in_progress, read_json_body, run_as_dev,
+    run_as_dev_timeout, shell_quote,
+};
+use crate::config::GIT_NETWORK_TIMEOUT_MS;
+use crate::limits::GIT_NETWORK_SEMAPHORE;
+use crate::response::json_ok;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FetchBody {
+    repo_path: String,
+    #[serde(default)]
+    remote: Option<String>,
+    #[serde(default)]
+    all: bool,
+    #[serde(default)]
+    prune: bool,
+    /// Also fetch Git LFS objects for the current branch.
+    #[serde(default)]
+    lfs: bool,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum PullStrategy {
+    #[default]
+    FfOnly,
+    Rebase,
+    Merge,
+}
+
+impl PullStrategy {
+    fn flag(self) -> &'static str {
+        match self {
+            PullStrategy::FfOnly => "--ff-only",
+            PullStrategy::Rebase => "--rebase",
+            PullStrategy::Merge => "--no-rebase",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullBody {
+    repo_path: String,
+    #[serde(default)]
+    strategy: PullStrategy,
+    #[serde(default)]
+    autostash: bool,
+    #[serde(default)]
+    remote: Option<String>,
+    #[serde(default)]
+    branch: Option<String>,
+    /// Also download Git LFS objects and check them out (`git lfs pull`).
+    #[serde(default)]
+    lfs: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitRefUpdate {
+    #[serde(rename = "ref")]
+    name: String,
+    old_hash: Option<String>,
+    new_hash: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitFetchResponse {
+    path: String,
+    success: bool,
+    updated: Vec<GitRefUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lfs_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitPulledCommit {
+    hash: String,
+    short_hash: String,
+    author: String,
+    date: String,
+    subject: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitPullResponse {
+    path: String,
+    success: bool,
+    strategy: PullStrategy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_head: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_head: Option<String>,
+    up_to_date: bool,
+    commits: Vec<GitPulledCommit>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    conflicts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation: Option<&'static str>,
+    /// The pull itself worked but the LFS download didn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lfs_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// `<remote> [<branch>]` arguments, or nothing to use the configured upstream.
+/// Either one starting with `-` would be taken as an option.
+fn remote_args(remote: Option<&str>, branch: Option<&str>) -> Result<String, String> {
+    if remote.is_some_and(|r| r.starts_with('-')) || branch.is_some_and(|b| b.starts_with('-')) {
+        return Err("Invalid remote or branch".to_string());
+    }
+    Ok(match (remote, branch) {
+        (Some(r), Some(b)) => format!(" {} {}", shell_quote(r), shell_quote(b)),
+        (Some(r), None) => format!(" {}", shell_quote(r)),
+        (None, Some(b)) => format!(" origin {}", shell_quote(b)),
+        (None, None) => String::new(),
+    })
+}
+
+/// Runs `git lfs <op>` against the given remote, or the default one.
+async fn run_lfs(path: &str, op: &str, remote: Option<&str>) -> Result<(), String> {
+    let (code, _, stderr) = run_as_dev_timeout(
+        &format!(
+            "GIT_TERMINAL_PROMPT=0 git -C '{path}' lfs {op}{}",
+            remote_args(remote, None)?
+        ),
+        GIT_NETWORK_TIMEOUT_MS,
+    )
+    .await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(())
+}
+
+async fn remote_refs(path: &str) -> BTreeMap<String, String> {
+    let (_, out, _) = run_as_dev(&format!(
+        "git -C '{path}' for-each-ref --format='%(refname:short) %(objectname)' refs/remotes"
+    ))
+    .await;
+    out.lines()
+        .filter_map(|l| l.split_once(' '))
+        .map(|(name, hash)| (name.to_string(), hash.to_string()))
+        .collect()
+}
+
+async fn head_hash(path: &str) -> Option<String> {
+    let (code, out, _) =
+        run_as_dev(&format!("git -C '{path}' rev-parse --verify --quiet HEAD")).await;
+    (code == 0).then(|| out.trim().to_string())
+}
+
+async fn fetch(body: FetchBody) -> GitFetchResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitFetchResponse {
+        path: body.repo_path,
+        ..Default::default()
+    };
+
+    let target = if body.all {
+        " --all".to_string()
+    } else {
+        match remote_args(body.remote.as_deref(), None) {
+            Ok(args) => args,
+            Err(e) => {
+                resp.error = Some(e);
+                return resp;
+            }
+        }
+    };
+    let before = remote_refs(&path).await;
+    let prune = if body.prune { " --prune" } else { "" };
+    let (code, _, stderr) = run_as_dev_timeout(
+        &format!("GIT_TERMINAL_PROMPT=0 git -C '{path}' fetch{prune}{target}"),
+        GIT_NETWORK_TIMEOUT_MS,
+    )
+    .await;
+    if code != 0 {
+        resp.error = Some(stderr.trim().to_string());
+        return resp;
+    }
+
+    let mut after = remote_refs(&path).await;
+    for (name, old) in before {
+        match after.remove(&name) {
+            Some(new) if new == old => {}
+            new_hash => resp.updated.push(GitRefUpdate {
+                name,
+                old_hash: Some(old),
+                new_hash,
+            }),
+        }
+    }
+    resp.updated
+        .extend(after.into_iter().map(|(name, new)| GitRefUpdate {
+            name,
+            old_hash: None,
+            new_hash: Some(new),
+        }));
+    resp.updated.sort_by(|a, b| a.name.cmp(&b.name));
+    if body.lfs
+        && let Err(e) = run_lfs(&path, "fetch", body.remote.as_deref()).await
+    {
+        resp.lfs_error = Some(e);
+    }
+    resp.success = true;
+    resp
+}
+
+/// Commits that arrived with the pull. `--cherry-pick` drops local commits a
+/// rebase rewrote, so only upstream work is listed.
+async fn pulled_commits(path: &str, old: &str, new: &str) -> Vec<GitPulledCommit> {
+    let (_, out, _) = run_as_dev(&format!(
+        "git -C '{path}' log --right-only --cherry-pick --format='%H%x00%h%x00%an%x00%aI%x00%s' {old}...{new}"
+    ))
+    .await;
+    out.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\0').collect();
+            let [hash, short_hash, author, date, subject] = fields.as_slice() else {
+                return None;
+            };
+            Some(GitPulledCommit {
+                hash: hash.to_string(),
+                short_hash: short_hash.to_string(),
+                author: author.to_string(),
+                date: date.to_string(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect()
+}
+
+async fn pull(body: PullBody) -> GitPullResponse {
+    let path = full_path(&body.repo_path);
+    let mut resp = GitPullResponse {
+        path: body.repo_path,
+        strategy: body.strategy,
+        ..Default::default()
+    };
+
+    if let Some(op) = operation_in_progress(&path).await {
+        resp.operation = Some(op);
+        resp.conflicts = conflicted_files(&path).await;
+        resp.error = Some(format!("A {op} is already in progress"));
+        return resp;
+    }
+
+    let target = match remote_args(body.remote.as_deref(), body.branch.as_deref()) {
+        Ok(args) => args,
+        Err(e) => {
+            resp.error = Some(e);
+            return resp;
+        }
+    };
+
+    resp.old_head = head_hash(&path).await;
+    let autostash = if body.autostash { " --autostash" } else { "" };
+    let (code, _, stderr) = run_as_dev_timeout(
+        &format!(
+            "GIT_TERMINAL_PROMPT=0 git -C '{path}' pull {}{autostash}{target}",
+            body.strategy.flag()
+        ),
+        GIT_NETWORK_TIMEOUT_MS,
+    )
+    .await;
+
+    resp.new_head = head_hash(&path).await;
+    resp.operation = operation_in_progress(&path).await;
+    // Conflicts also show up after a successful pull whose autostash didn't
+    // re-apply cleanly.
+    resp.conflicts = conflicted_files(&path).await;
+    resp.up_to_date = code == 0 && resp.old_head == resp.new_head;
+
+    if let (Some(old), Some(new)) = (&resp.old_head, &resp.new_head)
+        && old != new
+    {
+        resp.commits = pulled_commits(&path, old, new).await;
+    }
+
+    if code != 0 {
+        resp.error = Some(stderr.trim().to_string());
+        return resp;
+    }
+    if body.lfs
+        && let Err(e) = run_lfs(&path, "pull", body.remote.as_deref()).await
+    {
+        resp.lfs_error = Some(e);
+    }
+    resp.success = true;
+    resp
+}
+
+pub async fn handle_git_fetch(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: FetchBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => {
+            return json_ok(serde_json::json!({
+                "path": "", "success": false, "updated": [], "error": e
+            }));
+        }
+    };
+    let _permit = GIT_NETWORK_SEMAPHORE.acquire().await.unwrap();
+    json_ok(serde_json::to_value(fetch(body).await).unwrap())
+}
+
+pub async fn handle_git_pull(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: PullBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => {
+            return json_ok(serde_json::json!({
+                "path": "", "success": false, "commits": [], "error": e
+            }));
+        }
+    };
+    let _permit = GIT_NETWORK_SEMAPHORE.acquire().await.unwrap();
+    json_ok(serde_json::to_value(pull(body).await).unwrap())
+}
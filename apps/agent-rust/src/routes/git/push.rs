@@ -0,0 +1,292 @@
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{full_path, read_json_body, run_as_dev, run_as_dev_timeout, shell_quote};
+use crate::config::GIT_NETWORK_TIMEOUT_MS;
+use crate::limits::GIT_NETWORK_SEMAPHORE;
+use crate::response::json_ok;
+
+const MAX_PUSH_TIMEOUT_MS: u64 = 30 * 60 * 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PushBody {
+    repo_path: String,
+    /// Defaults to the branch's configured remote, then `origin`.
+    #[serde(default)]
+    remote: Option<String>,
+    /// Defaults to the current branch, pushed to the same name.
+    #[serde(default)]
+    refspec: Option<String>,
+    #[serde(default)]
+    force_with_lease: bool,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    /// Server-side push options (`--push-option`), e.g. GitLab's
+    /// `merge_request.create`.
+    #[serde(default)]
+    push_options: Vec<String>,
+}
+
+/// Why a push failed, for callers that react differently to each.
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum PushErrorCode {
+    NonFastForward,
+    AuthFailed,
+    ProtectedBranch,
+    HookRejected,
+    Network,
+    Unknown,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitPushResponse {
+    path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote: Option<String>,
+    /// Push URL with any embedded credentials stripped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote_url: Option<String>,
+    /// Local branch that was pushed, when pushing a branch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    /// Destination ref on the remote, e.g. `refs/heads/feature`.
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pushed_ref: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    new_branch: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    up_to_date: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    set_upstream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<PushErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitPushResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Drops `user:token@` from an http(s) URL.
+fn strip_credentials(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let host_end = rest.find('/').unwrap_or(rest.len());
+    match rest[..host_end].rfind('@') {
+        Some(at) => format!("{scheme}://{}", &rest[at + 1..]),
+        None => url.to_string(),
+    }
+}
+
+/// Maps git's push output onto an error code. Server messages vary by host;
+/// these cover GitHub, GitLab, Bitbucket and plain git servers.
+fn classify(output: &str, timed_out: bool) -> PushErrorCode {
+    let lower = output.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+
+    if timed_out {
+        return PushErrorCode::Network;
+    }
+    if has(&[
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "invalid username or password",
+        "permission denied (publickey)",
+        "permission to",
+        "the requested url returned error: 401",
+        "the requested url returned error: 403",
+    ]) {
+        return PushErrorCode::AuthFailed;
+    }
+    if has(&[
+        "protected branch",
+        "gh006",
+        "not allowed to push code to protected branches",
+        "not allowed to force push",
+    ]) {
+        return PushErrorCode::ProtectedBranch;
+    }
+    if has(&[
+        "(non-fast-forward)",
+        "(fetch first)",
+        "(stale info)",
+        "updates were rejected because",
+    ]) {
+        return PushErrorCode::NonFastForward;
+    }
+    if has(&[
+        "hook declined",
+        "pre-receive hook",
+        "pre-push hook",
+        "[remote rejected]",
+    ]) {
+        return PushErrorCode::HookRejected;
+    }
+    // Checked last: git adds "the remote end hung up" to refusals too, e.g.
+    // after an HTTP 403.
+    if has(&[
+        "could not resolve host",
+        "connection refused",
+        "connection timed out",
+        "operation timed out",
+        "network is unreachable",
+        "failed to connect",
+        "connection reset",
+        "the remote end hung up unexpectedly",
+        "early eof",
+    ]) {
+        return PushErrorCode::Network;
+    }
+    PushErrorCode::Unknown
+}
+
+async fn git_output(path: &str, args: &str) -> Option<String> {
+    let (code, out, _) = run_as_dev(&format!("git -C '{path}' {args}")).await;
+    let out = out.trim();
+    (code == 0 && !out.is_empty()).then(|| out.to_string())
+}
+
+async fn push(body: PushBody) -> GitPushResponse {
+    let path = full_path(&body.repo_path);
+
+    if body.remote.as_deref().is_some_and(|r| r.starts_with('-'))
+        || body.refspec.as_deref().is_some_and(|r| r.starts_with('-'))
+    {
+        return GitPushResponse::failed(body.repo_path, "Invalid remote or refspec");
+    }
+
+    let branch = git_output(&path, "branch --show-current").await;
+    if body.refspec.is_none() && branch.is_none() {
+        return GitPushResponse::failed(body.repo_path, "HEAD is detached; pass a refspec");
+    }
+    let branch_remote = match &branch {
+        Some(b) => {
+            git_output(
+                &path,
+                &format!("config {}", shell_quote(&format!("branch.{b}.remote"))),
+            )
+            .await
+        }
+        None => None,
+    };
+    let remote = body
+        .remote
+        .clone()
+        .or(branch_remote.clone())
+        .unwrap_or_else(|| "origin".to_string());
+
+    // Pushing the current branch without an upstream sets one, like the
+    // first `git push -u` a developer would run.
+    let refspec = body.refspec.clone().or(branch.clone()).unwrap_or_default();
+    let set_upstream = body.refspec.is_none() && branch_remote.is_none();
+
+    let mut flags = String::from(" --porcelain");
+    if set_upstream {
+        flags.push_str(" --set-upstream");
+    }
+    if body.force_with_lease {
+        flags.push_str(" --force-with-lease");
+    }
+    for option in &body.push_options {
+        flags.push_str(&format!(" --push-option={}", shell_quote(option)));
+    }
+
+    let timeout = body
+        .timeout_ms
+        .unwrap_or(GIT_NETWORK_TIMEOUT_MS)
+        .clamp(1000, MAX_PUSH_TIMEOUT_MS);
+    let (code, stdout, stderr) = run_as_dev_timeout(
+        &format!(
+            "GIT_TERMINAL_PROMPT=0 git -C '{path}' push{flags} {} {}",
+            shell_quote(&remote),
+            shell_quote(&refspec)
+        ),
+        timeout,
+    )
+    .await;
+
+    // Porcelain output: "To <url>", then "<flag>\t<from>:<to>\t<summary>"
+    // per ref, then "Done".
+    let mut remote_url = None;
+    let mut pushed_ref = None;
+    let mut new_branch = false;
+    let mut up_to_date = false;
+    for line in stdout.lines() {
+        if let Some(url) = line.strip_prefix("To ") {
+            remote_url = Some(strip_credentials(url.trim()));
+            continue;
+        }
+        let mut parts = line.splitn(3, '\t');
+        let (Some(flag), Some(refs), Some(summary)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if pushed_ref.is_none() {
+            pushed_ref = refs.split_once(':').map(|(_, to)| to.to_string());
+            new_branch = flag == "*";
+            up_to_date = flag == "=" || summary.contains("[up to date]");
+        }
+    }
+    if remote_url.is_none() {
+        remote_url = git_output(
+            &path,
+            &format!("remote get-url --push {}", shell_quote(&remote)),
+        )
+        .await
+        .or_else(|| remote.contains("://").then(|| remote.clone()))
+        .map(|u| strip_credentials(&u));
+    }
+
+    let mut resp = GitPushResponse {
+        path: body.repo_path,
+        success: code == 0,
+        remote: Some(strip_credentials(&remote)),
+        remote_url,
+        branch: if body.refspec.is_none() { branch } else { None },
+        pushed_ref,
+        new_branch: code == 0 && new_branch,
+        up_to_date: code == 0 && up_to_date,
+        set_upstream: code == 0 && set_upstream,
+        ..Default::default()
+    };
+    if code != 0 {
+        let output = format!("{stdout}\n{stderr}");
+        let timed_out = stderr.contains("Command timed out");
+        resp.code = Some(classify(&output, timed_out));
+        resp.error = Some(if timed_out {
+            format!("Push timed out after {timeout}ms")
+        } else {
+            stderr.trim().to_string()
+        });
+    }
+    resp
+}
+
+pub async fn handle_git_push(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    let body: PushBody = match read_json_body(req).await {
+        Ok(b) => b,
+        Err(e) => {
+            return json_ok(
+                serde_json::to_value(GitPushResponse::failed(String::new(), e)).unwrap(),
+            );
+        }
+    };
+    let _permit = GIT_NETWORK_SEMAPHORE.acquire().await.unwrap();
+    json_ok(serde_json::to_value(push(body).await).unwrap())
+}
@@ -0,0 +1,354 @@
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    GitDiffFile, conflicted_files, full_path, handle_json_request, run_as_dev, shell_quote,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StashListBody {
+    repo_path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StashPushBody {
+    repo_path: String,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    include_untracked: bool,
+    #[serde(default)]
+    paths: Vec<String>,
+}
+
+/// Targets one entry; `index` 0 is the most recent stash.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StashEntryBody {
+    repo_path: String,
+    #[serde(default)]
+    index: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitStashEntry {
+    index: u32,
+    #[serde(rename = "ref")]
+    name: String,
+    hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    message: String,
+    date: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitStashListResponse {
+    path: String,
+    stashes: Vec<GitStashEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitStashShowResponse {
+    path: String,
+    files: Vec<GitDiffFile>,
+    total_added: u32,
+    total_removed: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitStashActionResponse {
+    path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stash: Option<GitStashEntry>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    dropped: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    conflicts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitStashActionResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+fn stash_ref(index: u32) -> String {
+    format!("'stash@{{{index}}}'")
+}
+
+/// Splits a reflog subject ("WIP on main: abc123 subject" or
+/// "On main: message") into branch and message.
+fn parse_subject(subject: &str) -> (Option<String>, String) {
+    let rest = subject
+        .strip_prefix("WIP on ")
+        .or_else(|| subject.strip_prefix("On "));
+    match rest.and_then(|r| r.split_once(": ")) {
+        Some((branch, message)) if branch != "(no branch)" => {
+            (Some(branch.to_string()), message.to_string())
+        }
+        Some((_, message)) => (None, message.to_string()),
+        None => (None, subject.to_string()),
+    }
+}
+
+async fn stash_entries(path: &str) -> Result<Vec<GitStashEntry>, String> {
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' stash list --format='%gd%x00%H%x00%gs%x00%cI'"
+    ))
+    .await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+
+    Ok(out
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let fields: Vec<&str> = line.split('\0').collect();
+            let [name, hash, subject, date] = fields.as_slice() else {
+                return None;
+            };
+            let (branch, message) = parse_subject(subject);
+            Some(GitStashEntry {
+                index: index as u32,
+                name: name.to_string(),
+                hash: hash.to_string(),
+                branch,
+                message,
+                date: date.to_string(),
+            })
+        })
+        .collect())
+}
+
+async fn list(body: StashListBody) -> GitStashListResponse {
+    let path = full_path(&body.repo_path);
+    match stash_entries(&path).await {
+        Ok(stashes) => GitStashListResponse {
+            path: body.repo_path,
+            stashes,
+            error: None,
+        },
+        Err(e) => GitStashListResponse {
+            path: body.repo_path,
+            error: Some(e),
+            ..Default::default()
+        },
+    }
+}
+
+async fn push(body: StashPushBody) -> GitStashActionResponse {
+    let path = full_path(&body.repo_path);
+    let before = stash_entries(&path).await.unwrap_or_default();
+
+    let mut flags = String::new();
+    if body.include_untracked {
+        flags.push_str(" --include-untracked");
+    }
+    if let Some(message) = body.message.as_deref().filter(|m| !m.is_empty()) {
+        flags.push_str(&format!(" -m {}", shell_quote(message)));
+    }
+    if !body.paths.is_empty() {
+        let quoted: Vec<String> = body.paths.iter().map(|p| shell_quote(p)).collect();
+        flags.push_str(&format!(" -- {}", quoted.join(" ")));
+    }
+
+    let (code, _, stderr) = run_as_dev(&format!("git -C '{path}' stash push{flags}")).await;
+    if code != 0 {
+        return GitStashActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    // "No local changes to save" exits 0 without creating an entry.
+    let stash = stash_entries(&path)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .filter(|top| before.first().is_none_or(|prev| prev.hash != top.hash));
+
+    GitStashActionResponse {
+        path: body.repo_path,
+        success: true,
+        stash,
+        ..Default::default()
+    }
+}
+
+async fn show(body: StashEntryBody) -> GitStashShowResponse {
+    let path = full_path(&body.repo_path);
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' stash show --numstat --include-untracked {}",
+        stash_ref(body.index)
+    ))
+    .await;
+    if code != 0 {
+        return GitStashShowResponse {
+            path: body.repo_path,
+            error: Some(stderr.trim().to_string()),
+            ..Default::default()
+        };
+    }
+
+    let files: Vec<GitDiffFile> = out
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let (added, removed, file) = (parts.next()?, parts.next()?, parts.next()?);
+            Some(GitDiffFile {
+                path: file.to_string(),
+                added: added.parse().unwrap_or(0),
+                removed: removed.parse().unwrap_or(0),
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    GitStashShowResponse {
+        path: body.repo_path,
+        total_added: files.iter().map(|f| f.added).sum(),
+        total_removed: files.iter().map(|f| f.removed).sum(),
+        files,
+        error: None,
+    }
+}
+
+async fn apply(body: StashEntryBody, pop: bool) -> GitStashActionResponse {
+    let path = full_path(&body.repo_path);
+    let stash = stash_entries(&path)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|s| s.index == body.index);
+    if stash.is_none() {
+        return GitStashActionResponse::failed(
+            body.repo_path,
+            format!("No stash at index {}", body.index),
+        );
+    }
+
+    let action = if pop { "pop" } else { "apply" };
+    let (code, _, stderr) = run_as_dev(&format!(
+        "git -C '{path}' stash {action} {}",
+        stash_ref(body.index)
+    ))
+    .await;
+
+    // A conflicting apply exits 1 and leaves conflict markers; pop then
+    // keeps the entry so nothing is lost.
+    let conflicts = conflicted_files(&path).await;
+    let success = code == 0 && conflicts.is_empty();
+    let error = (!success).then(|| {
+        if conflicts.is_empty() {
+            stderr.trim().to_string()
+        } else {
+            format!("Stash {action} resulted in conflicts")
+        }
+    });
+
+    GitStashActionResponse {
+        path: body.repo_path,
+        success,
+        stash,
+        dropped: pop && success,
+        conflicts,
+        error,
+    }
+}
+
+async fn drop_entry(body: StashEntryBody) -> GitStashActionResponse {
+    let path = full_path(&body.repo_path);
+    let stash = stash_entries(&path)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|s| s.index == body.index);
+    if stash.is_none() {
+        return GitStashActionResponse::failed(
+            body.repo_path,
+            format!("No stash at index {}", body.index),
+        );
+    }
+
+    let (code, _, stderr) = run_as_dev(&format!(
+        "git -C '{path}' stash drop {}",
+        stash_ref(body.index)
+    ))
+    .await;
+    if code != 0 {
+        return GitStashActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    GitStashActionResponse {
+        path: body.repo_path,
+        success: true,
+        stash,
+        dropped: true,
+        ..Default::default()
+    }
+}
+
+pub async fn handle_git_stash_list(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(
+        req,
+        |_, e| GitStashListResponse {
+            error: Some(e),
+            ..Default::default()
+        },
+        list,
+    )
+    .await
+}
+
+pub async fn handle_git_stash_push(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitStashActionResponse::failed, push).await
+}
+
+pub async fn handle_git_stash_show(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(
+        req,
+        |_, e| GitStashShowResponse {
+            error: Some(e),
+            ..Default::default()
+        },
+        show,
+    )
+    .await
+}
+
+pub async fn handle_git_stash_apply(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitStashActionResponse::failed, |body| {
+        apply(body, false)
+    })
+    .await
+}
+
+pub async fn handle_git_stash_pop(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitStashActionResponse::failed, |body| {
+        apply(body, true)
+    })
+    .await
+}
+
+pub async fn handle_git_stash_drop(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitStashActionResponse::failed, drop_entry).await
+}
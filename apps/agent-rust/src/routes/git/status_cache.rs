@@ -0,0 +1,519 @@
+// Push-based status for the configured repos. Each repo's `GitRepoStatus` is
+// cached and only recomputed when something that can change it moves: a
+// worktree file (the file index already watches every non-ignored
+// directory and forwards its events here) or the repo's git dir (HEAD, the
+// index, refs, reflogs), which is watched below along with its submodules'
+// git dirs.
+// Changes that alter a repo's status are published as events, which
+// `GET /git/events` long-polls for. When some of a repo's directories
+// couldn't be watched, its status is always recomputed; so is any entry
+// older than `MAX_CACHE_AGE`, in case a change slipped past the watches.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use http_body_util::Full;
+use hyper::Response;
+use hyper::body::Bytes;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tokio::sync::{mpsc, watch};
+
+use super::{GitRepoStatus, full_path, native, repo_status};
+use crate::config::get_config;
+use crate::limits::GIT_SEMAPHORE;
+use crate::response::json_ok;
+use crate::utc_rfc3339;
+
+// A commit or checkout touches the index, HEAD and refs in quick
+// succession; recompute once for the lot.
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(200);
+// Events kept for `since`; a client further behind gets a snapshot.
+const MAX_BUFFERED_EVENTS: usize = 256;
+const DEFAULT_EVENTS_WAIT_MS: u64 = 25_000;
+const MAX_EVENTS_WAIT_MS: u64 = 60_000;
+const MAX_CACHE_AGE: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+struct CachedStatus {
+    status: GitRepoStatus,
+    at: Instant,
+}
+
+struct WatchedRepo {
+    clone_path: String,
+    root: PathBuf,
+    /// Holds refs and packed-refs; differs from the git dir for linked
+    /// worktrees.
+    common_dir: PathBuf,
+    /// Watched git dirs of submodules, under `<common_dir>/modules`.
+    module_dirs: Vec<PathBuf>,
+    /// `<common_dir>/logs` is watched; it only appears with the first commit.
+    logs_watched: bool,
+    /// A git dir watch failed; see `WatchedRepo::cache_usable`.
+    watch_failed: bool,
+    /// Bumped on every change, so a status computed across one isn't cached.
+    generation: u64,
+    plain: Option<CachedStatus>,
+    detailed: Option<CachedStatus>,
+    /// Last status sent out as an event; recomputes that come out the same
+    /// (an editor touching a file, git refreshing the index) publish nothing.
+    published: Option<GitRepoStatus>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitStatusEvent {
+    seq: u64,
+    at: String,
+    status: GitRepoStatus,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GitEventsResponse {
+    events: Vec<GitStatusEvent>,
+    /// Pass as `since` on the next call.
+    next_seq: u64,
+    /// `events` holds the current status of every watched repo instead of
+    /// changes: the first call, or `since` is no longer buffered.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    snapshot: bool,
+}
+
+static REPOS: LazyLock<Mutex<Vec<WatchedRepo>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+static EVENTS: LazyLock<Mutex<VecDeque<GitStatusEvent>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+static LATEST_SEQ: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::Sender::new(0));
+static WATCHER: LazyLock<Mutex<Option<RecommendedWatcher>>> = LazyLock::new(|| Mutex::new(None));
+static CHANGED: OnceLock<mpsc::UnboundedSender<String>> = OnceLock::new();
+
+impl WatchedRepo {
+    /// Whether every change to the repo is seen, so a cached status can be
+    /// trusted.
+    fn cache_usable(&self) -> bool {
+        !self.watch_failed && crate::file_index::fully_watched(&self.root)
+    }
+
+    fn cached(&self, detailed: bool) -> Option<GitRepoStatus> {
+        let entry = if detailed {
+            self.detailed.as_ref()
+        } else {
+            self.plain.as_ref()
+        };
+        entry
+            .filter(|c| c.at.elapsed() < MAX_CACHE_AGE && self.cache_usable())
+            .map(|c| c.status.clone())
+    }
+}
+
+/// Git dirs of the submodules under `<common_dir>/modules`, nested ones
+/// included. A submodule's name may contain `/`, so a directory without a
+/// `HEAD` is searched further.
+fn module_git_dirs(modules: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(modules) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+        if dir.join("HEAD").is_file() {
+            module_git_dirs(&dir.join("modules"), out);
+            out.push(dir);
+        } else {
+            module_git_dirs(&dir, out);
+        }
+    }
+}
+
+/// Watches the git dirs of submodules initialized since the last call, so
+/// commits and checkouts inside a submodule reach the parent's status.
+fn watch_submodules(clone_path: &str) {
+    let (common_dir, watched) = {
+        let repos = REPOS.lock().unwrap();
+        let Some(repo) = repos.iter().find(|r| r.clone_path == clone_path) else {
+            return;
+        };
+        (repo.common_dir.clone(), repo.module_dirs.clone())
+    };
+    let mut found = Vec::new();
+    module_git_dirs(&common_dir.join("modules"), &mut found);
+    found.retain(|d| !watched.contains(d));
+    if found.is_empty() {
+        return;
+    }
+
+    let mut failed = false;
+    {
+        let mut guard = WATCHER.lock().unwrap();
+        let Some(watcher) = guard.as_mut() else {
+            return;
+        };
+        for dir in &found {
+            if let Err(e) = watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .and_then(|()| watcher.watch(&dir.join("refs"), RecursiveMode::Recursive))
+            {
+                eprintln!("git status: failed to watch {}: {e}", dir.display());
+                failed = true;
+            }
+        }
+    }
+    let mut repos = REPOS.lock().unwrap();
+    if let Some(repo) = repos.iter_mut().find(|r| r.clone_path == clone_path) {
+        repo.module_dirs.extend(found);
+        repo.watch_failed |= failed;
+    }
+}
+
+/// Watches the repo's reflogs once it has them. Dropping any stash but the
+/// newest only rewrites `logs/refs/stash`, which is where `stashCount` comes
+/// from.
+fn watch_reflogs(clone_path: &str) {
+    let logs = {
+        let repos = REPOS.lock().unwrap();
+        let Some(repo) = repos
+            .iter()
+            .find(|r| r.clone_path == clone_path && !r.logs_watched)
+        else {
+            return;
+        };
+        repo.common_dir.join("logs")
+    };
+    if !logs.is_dir() {
+        return;
+    }
+
+    let result = {
+        let mut guard = WATCHER.lock().unwrap();
+        let Some(watcher) = guard.as_mut() else {
+            return;
+        };
+        watcher.watch(&logs, RecursiveMode::Recursive)
+    };
+    if let Err(e) = &result {
+        eprintln!("git status: failed to watch {}: {e}", logs.display());
+    }
+    let mut repos = REPOS.lock().unwrap();
+    if let Some(repo) = repos.iter_mut().find(|r| r.clone_path == clone_path) {
+        repo.logs_watched = true;
+        repo.watch_failed |= result.is_err();
+    }
+}
+
+/// Marks the repos `events` touch as changed. Called from both watchers.
+pub fn invalidate(events: &[Event]) {
+    let Some(tx) = CHANGED.get() else {
+        return;
+    };
+    let mut repos = REPOS.lock().unwrap();
+    for repo in repos.iter_mut() {
+        let touched = events.iter().any(|e| {
+            // Reads don't count, git's own while computing status included.
+            let relevant = !matches!(e.kind, EventKind::Access(_))
+                && e.paths
+                    .iter()
+                    .any(|p| p.starts_with(&repo.root) || p.starts_with(&repo.common_dir));
+            relevant || e.need_rescan()
+        });
+        if touched {
+            repo.generation += 1;
+            repo.plain = None;
+            repo.detailed = None;
+            let _ = tx.send(repo.clone_path.clone());
+        }
+    }
+}
+
+/// Starts watching configured repos that exist and aren't watched yet.
+/// Repos are usually cloned after the agent boots, so this also runs on
+/// every status request.
+fn watch_new_repos() {
+    let Some(cfg) = get_config() else {
+        return;
+    };
+    let missing: Vec<String> = {
+        let repos = REPOS.lock().unwrap();
+        cfg.repos
+            .iter()
+            .map(|r| r.clone_path.trim_end_matches('/').to_string())
+            .filter(|c| !repos.iter().any(|r| &r.clone_path == c))
+            .collect()
+    };
+    if missing.is_empty() {
+        return;
+    }
+
+    let mut added = Vec::new();
+    {
+        // REPOS must not be held here: the watcher's callback takes it, and
+        // `watch` waits on the thread that runs the callback.
+        let mut guard = WATCHER.lock().unwrap();
+        let Some(watcher) = guard.as_mut() else {
+            return;
+        };
+        for clone_path in missing {
+            let Some(repo) = native::open(&full_path(&clone_path)) else {
+                continue;
+            };
+            let canonical = |p: &Path| std::fs::canonicalize(p).ok();
+            let (Some(root), Some(git_dir), Some(common_dir)) = (
+                repo.workdir().and_then(canonical),
+                canonical(repo.path()),
+                canonical(repo.commondir()),
+            ) else {
+                continue;
+            };
+            if let Err(e) = watcher
+                .watch(&git_dir, RecursiveMode::NonRecursive)
+                .and_then(|()| watcher.watch(&common_dir.join("refs"), RecursiveMode::Recursive))
+            {
+                eprintln!("git status: failed to watch {clone_path}: {e}");
+                continue;
+            }
+            let watch_failed = common_dir != git_dir
+                && watcher
+                    .watch(&common_dir, RecursiveMode::NonRecursive)
+                    .is_err();
+            added.push(WatchedRepo {
+                clone_path,
+                root,
+                common_dir,
+                module_dirs: Vec::new(),
+                logs_watched: false,
+                watch_failed,
+                generation: 0,
+                plain: None,
+                detailed: None,
+                published: None,
+            });
+        }
+    }
+    if added.is_empty() {
+        return;
+    }
+
+    // Worktree changes come through the file index's watches.
+    crate::file_index::ensure_indexes();
+    let clone_paths: Vec<String> = added.iter().map(|r| r.clone_path.clone()).collect();
+    REPOS.lock().unwrap().extend(added);
+    for clone_path in clone_paths {
+        watch_reflogs(&clone_path);
+        watch_submodules(&clone_path);
+        if let Some(tx) = CHANGED.get() {
+            let _ = tx.send(clone_path);
+        }
+    }
+}
+
+pub(super) async fn ensure_watched() {
+    let _ = tokio::task::spawn_blocking(watch_new_repos).await;
+}
+
+fn publish(status: GitRepoStatus) {
+    let mut events = EVENTS.lock().unwrap();
+    let seq = *LATEST_SEQ.borrow() + 1;
+    events.push_back(GitStatusEvent {
+        seq,
+        at: utc_rfc3339(),
+        status,
+    });
+    while events.len() > MAX_BUFFERED_EVENTS {
+        events.pop_front();
+    }
+    LATEST_SEQ.send_replace(seq);
+}
+
+fn generation(clone_path: &str) -> Option<u64> {
+    let repos = REPOS.lock().unwrap();
+    repos
+        .iter()
+        .find(|r| r.clone_path == clone_path)
+        .map(|r| r.generation)
+}
+
+/// Recomputes a changed repo and publishes its status if it differs from
+/// the last one sent.
+async fn refresh(clone_path: String) {
+    let Some(generation) = generation(&clone_path) else {
+        return;
+    };
+    let status = {
+        let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+        repo_status(clone_path.clone(), false).await
+    };
+    let path = clone_path.clone();
+    let has_submodules = !status.submodules.is_empty();
+    let _ = tokio::task::spawn_blocking(move || {
+        watch_reflogs(&path);
+        if has_submodules {
+            watch_submodules(&path);
+        }
+    })
+    .await;
+
+    let changed = {
+        let mut repos = REPOS.lock().unwrap();
+        let Some(pos) = repos.iter().position(|r| r.clone_path == clone_path) else {
+            return;
+        };
+        let repo = &mut repos[pos];
+        let changed = repo.published.as_ref() != Some(&status);
+        if status.error.is_some() {
+            // Deleted, or about to be re-cloned: its watches are gone, so
+            // start over on the next status request.
+            repos.remove(pos);
+        } else {
+            if repo.generation == generation {
+                repo.plain = Some(CachedStatus {
+                    status: status.clone(),
+                    at: Instant::now(),
+                });
+            }
+            repo.published = Some(status.clone());
+        }
+        changed
+    };
+    if changed {
+        publish(status);
+    }
+}
+
+/// Status of one repo, from the cache when it's watched and unchanged.
+pub(super) async fn cached_status(clone_path: String, detailed: bool) -> GitRepoStatus {
+    let key = clone_path.trim_end_matches('/');
+    let generation = {
+        let repos = REPOS.lock().unwrap();
+        let repo = repos.iter().find(|r| r.clone_path == key);
+        if let Some(mut status) = repo.and_then(|r| r.cached(detailed)) {
+            status.path = clone_path;
+            return status;
+        }
+        repo.map(|r| r.generation)
+    };
+
+    let status = {
+        let _permit = GIT_SEMAPHORE.acquire().await.unwrap();
+        repo_status(clone_path.clone(), detailed).await
+    };
+    if let Some(generation) = generation {
+        let mut repos = REPOS.lock().unwrap();
+        if let Some(repo) = repos
+            .iter_mut()
+            .find(|r| r.clone_path == key && r.generation == generation)
+            && status.error.is_none()
+        {
+            let entry = Some(CachedStatus {
+                status: status.clone(),
+                at: Instant::now(),
+            });
+            if detailed {
+                repo.detailed = entry;
+            } else {
+                repo.plain = entry;
+            }
+        }
+    }
+    status
+}
+
+pub async fn start() {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let _ = CHANGED.set(tx);
+    let watcher = notify::recommended_watcher(|res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            invalidate(&[event]);
+        }
+    });
+    match watcher {
+        Ok(w) => *WATCHER.lock().unwrap() = Some(w),
+        Err(e) => eprintln!("git status: watcher unavailable, status will not be cached: {e}"),
+    }
+
+    ensure_watched().await;
+
+    while let Some(first) = rx.recv().await {
+        let mut changed = vec![first];
+        tokio::time::sleep(EVENT_DEBOUNCE).await;
+        while let Ok(clone_path) = rx.try_recv() {
+            if !changed.contains(&clone_path) {
+                changed.push(clone_path);
+            }
+        }
+        for clone_path in changed {
+            refresh(clone_path).await;
+        }
+    }
+}
+
+fn snapshot() -> GitEventsResponse {
+    let next_seq = *LATEST_SEQ.borrow();
+    let at = utc_rfc3339();
+    let repos = REPOS.lock().unwrap();
+    GitEventsResponse {
+        events: repos
+            .iter()
+            .filter_map(|r| r.published.clone())
+            .map(|status| GitStatusEvent {
+                seq: next_seq,
+                at: at.clone(),
+                status,
+            })
+            .collect(),
+        next_seq,
+        snapshot: true,
+    }
+}
+
+/// `GET /git/events?since=<seq>&timeoutMs=<ms>`: status changes after
+/// `since`, waiting up to `timeoutMs` for one when there are none yet.
+/// Without `since`, returns the current status of every watched repo.
+pub async fn handle_git_events(query: &str) -> Response<Full<Bytes>> {
+    let mut since: Option<u64> = None;
+    let mut timeout_ms = DEFAULT_EVENTS_WAIT_MS;
+    for param in query.split('&') {
+        let mut kv = param.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("since"), Some(v)) => since = v.parse().ok(),
+            (Some("timeoutMs"), Some(v)) => timeout_ms = v.parse().unwrap_or(timeout_ms),
+            _ => {}
+        }
+    }
+    let timeout = Duration::from_millis(timeout_ms.min(MAX_EVENTS_WAIT_MS));
+
+    ensure_watched().await;
+    let mut latest = LATEST_SEQ.subscribe();
+    let Some(since) = since else {
+        return json_ok(serde_json::to_value(snapshot()).unwrap());
+    };
+    // A `since` ahead of us means the agent restarted.
+    let oldest = EVENTS.lock().unwrap().front().map(|e| e.seq);
+    let current = *latest.borrow_and_update();
+    if since > current || oldest.is_some_and(|o| o > since + 1) {
+        return json_ok(serde_json::to_value(snapshot()).unwrap());
+    }
+
+    if current == since {
+        let _ = tokio::time::timeout(timeout, latest.wait_for(|seq| *seq > since)).await;
+    }
+    let events: Vec<GitStatusEvent> = EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.seq > since)
+        .cloned()
+        .collect();
+    let next_seq = events.last().map_or(since, |e| e.seq);
+    json_ok(
+        serde_json::to_value(GitEventsResponse {
+            events,
+            next_seq,
+            snapshot: false,
+        })
+        .unwrap(),
+    )
+}
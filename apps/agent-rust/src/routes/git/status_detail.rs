@@ -0,0 +1,263 @@
+use std::io::Read;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::{GitRepoStatus, full_path, lfs, run_as_dev};
+
+// Untracked files bigger than this get no line count; reading them whole
+// would cost more than the number is worth.
+const MAX_LINE_COUNT_BYTES: u64 = 8 * 1024 * 1024;
+// Same heuristic as git: a NUL in the first 8000 bytes means binary.
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GitFileStatus {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orig_path: Option<String>,
+    index: &'static str,
+    worktree: &'static str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    untracked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conflict: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    binary: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    submodule: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    lfs: bool,
+}
+
+#[derive(Default)]
+struct PorcelainStatus {
+    head: Option<String>,
+    ahead: u32,
+    behind: u32,
+    stash_count: u32,
+    files: Vec<GitFileStatus>,
+}
+
+fn state_name(code: u8) -> &'static str {
+    match code {
+        b'M' => "modified",
+        b'T' => "typechange",
+        b'A' => "added",
+        b'D' => "deleted",
+        b'R' => "renamed",
+        b'C' => "copied",
+        b'U' => "unmerged",
+        _ => "unmodified",
+    }
+}
+
+fn conflict_name(xy: &[u8]) -> &'static str {
+    match xy {
+        b"DD" => "both_deleted",
+        b"AU" => "added_by_us",
+        b"UD" => "deleted_by_them",
+        b"UA" => "added_by_them",
+        b"DU" => "deleted_by_us",
+        b"AA" => "both_added",
+        _ => "both_modified",
+    }
+}
+
+/// `sub` is the porcelain v2 submodule field: `N...` for a plain file,
+/// `S<c><m><u>` for a submodule.
+fn tracked_entry(xy: &str, sub: &str, path: &str, orig_path: Option<String>) -> GitFileStatus {
+    let xy = xy.as_bytes();
+    GitFileStatus {
+        path: path.to_string(),
+        orig_path,
+        index: state_name(xy.first().copied().unwrap_or(b'.')),
+        worktree: state_name(xy.get(1).copied().unwrap_or(b'.')),
+        untracked: false,
+        conflict: None,
+        lines: None,
+        binary: false,
+        submodule: sub.starts_with('S'),
+        lfs: false,
+    }
+}
+
+/// Parses `git status --porcelain=v2 --branch --show-stash -z` output.
+fn parse_porcelain_v2(out: &str) -> PorcelainStatus {
+    let mut status = PorcelainStatus::default();
+    let mut fields = out.split('\0');
+
+    while let Some(record) = fields.next() {
+        if let Some(header) = record.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.head" if value != "(detached)" => status.head = Some(value.to_string()),
+                "branch.ab" => {
+                    let mut parts = value.split_whitespace();
+                    status.ahead = parts
+                        .next()
+                        .and_then(|a| a.trim_start_matches('+').parse().ok())
+                        .unwrap_or(0);
+                    status.behind = parts
+                        .next()
+                        .and_then(|b| b.trim_start_matches('-').parse().ok())
+                        .unwrap_or(0);
+                }
+                "stash" => status.stash_count = value.parse().unwrap_or(0),
+                _ => {}
+            }
+            continue;
+        }
+
+        let mut parts = record.splitn(2, ' ');
+        let (Some(kind), Some(rest)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        match kind {
+            // 1 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <path>
+            "1" => {
+                let cols: Vec<&str> = rest.splitn(8, ' ').collect();
+                if let [xy, sub, .., path] = cols.as_slice() {
+                    status.files.push(tracked_entry(xy, sub, path, None));
+                }
+            }
+            // 2 <XY> <sub> <mH> <mI> <mW> <hH> <hI> <X><score> <path> NUL <origPath>
+            "2" => {
+                let cols: Vec<&str> = rest.splitn(9, ' ').collect();
+                let orig = fields.next().map(str::to_string);
+                if let [xy, sub, .., path] = cols.as_slice() {
+                    status.files.push(tracked_entry(xy, sub, path, orig));
+                }
+            }
+            // u <XY> <sub> <m1> <m2> <m3> <mW> <h1> <h2> <h3> <path>
+            "u" => {
+                let cols: Vec<&str> = rest.splitn(10, ' ').collect();
+                if let [xy, sub, .., path] = cols.as_slice() {
+                    let mut entry = tracked_entry(xy, sub, path, None);
+                    entry.conflict = Some(conflict_name(xy.as_bytes()));
+                    status.files.push(entry);
+                }
+            }
+            "?" => status.files.push(GitFileStatus {
+                path: rest.to_string(),
+                orig_path: None,
+                index: "unmodified",
+                worktree: "added",
+                untracked: true,
+                conflict: None,
+                lines: None,
+                binary: false,
+                submodule: false,
+                lfs: false,
+            }),
+            _ => {}
+        }
+    }
+
+    status
+}
+
+/// Returns `(lines, binary)` for an untracked file, `lines` being `None` when
+/// the file is binary, too large or unreadable.
+pub(super) fn count_lines(path: &Path) -> (Option<u32>, bool) {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return (None, false);
+    };
+    if !meta.is_file() || meta.len() > MAX_LINE_COUNT_BYTES {
+        return (None, false);
+    }
+    let mut buf = Vec::with_capacity(meta.len() as usize);
+    if std::fs::File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut buf))
+        .is_err()
+    {
+        return (None, false);
+    }
+    if buf[..buf.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return (None, true);
+    }
+    let newlines = buf.iter().filter(|b| **b == b'\n').count();
+    let trailing = usize::from(buf.last().is_some_and(|b| *b != b'\n'));
+    (Some((newlines + trailing) as u32), false)
+}
+
+pub(super) async fn get_repo_status_detailed(clone_path: String) -> GitRepoStatus {
+    let path = full_path(&clone_path);
+
+    let (code, out, stderr) = run_as_dev(&format!(
+        "git -C '{path}' --no-optional-locks status --porcelain=v2 --branch --show-stash -z \
+         --untracked-files=all"
+    ))
+    .await;
+    if code != 0 {
+        let error = if stderr.contains("not a git repository") {
+            "Not a git repository".to_string()
+        } else {
+            stderr.trim().to_string()
+        };
+        return GitRepoStatus {
+            path: clone_path,
+            branch: None,
+            dirty: false,
+            ahead: 0,
+            behind: 0,
+            last_commit: None,
+            files: None,
+            stash_count: None,
+            submodules: Vec::new(),
+            error: Some(error),
+        };
+    }
+
+    let mut status = parse_porcelain_v2(&out);
+
+    let names: Vec<&str> = status
+        .files
+        .iter()
+        .filter(|f| !f.submodule)
+        .map(|f| f.path.as_str())
+        .collect();
+    let tracked = lfs::lfs_paths(&path, &names).await;
+    for file in status.files.iter_mut() {
+        file.lfs = tracked.contains(&file.path);
+    }
+
+    let root = Path::new(&path).to_path_buf();
+    let files = std::mem::take(&mut status.files);
+    let files = tokio::task::spawn_blocking(move || {
+        files
+            .into_iter()
+            .map(|mut f| {
+                if f.untracked {
+                    (f.lines, f.binary) = count_lines(&root.join(&f.path));
+                }
+                f
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let (_, commit_out, _) = run_as_dev(&format!("git -C '{path}' log -1 --format='%h %s'")).await;
+    let commit = commit_out.trim().to_string();
+
+    GitRepoStatus {
+        path: clone_path,
+        branch: status.head,
+        dirty: !files.is_empty(),
+        ahead: status.ahead,
+        behind: status.behind,
+        last_commit: if commit.is_empty() {
+            None
+        } else {
+            Some(commit)
+        },
+        files: Some(files),
+        stash_count: Some(status.stash_count),
+        submodules: Vec::new(),
+        error: None,
+    }
+}
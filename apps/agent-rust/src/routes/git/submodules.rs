@@ -0,0 +1,126 @@
+// Submodules. The parent repo only records a commit hash per submodule (a
+// gitlink), so its own status and diff can't see inside one; each
+// initialized submodule is reported as a nested repo instead.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::{GitRepoStatus, full_path, repo_status, run_as_dev};
+
+/// Mode of a gitlink entry in the index.
+const GITLINK_MODE: &str = "160000";
+
+pub(super) struct Submodule {
+    /// Relative to the parent's root.
+    pub(super) path: String,
+    /// Commit the parent's index records.
+    recorded_commit: String,
+}
+
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct GitSubmoduleStatus {
+    #[serde(flatten)]
+    pub(super) status: GitRepoStatus,
+    initialized: bool,
+    recorded_commit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    head_commit: Option<String>,
+    /// The checked-out commit isn't the one the parent records: the
+    /// submodule moved without the parent being updated, or the other way
+    /// round.
+    pub(super) out_of_sync: bool,
+}
+
+/// The repo's submodules, from the gitlinks in its index.
+pub(super) async fn submodules(path: &str) -> Vec<Submodule> {
+    let (code, out, _) = run_as_dev(&format!("git -C '{path}' ls-files -s -z")).await;
+    if code != 0 {
+        return Vec::new();
+    }
+    let mut subs: Vec<Submodule> = Vec::new();
+    // `<mode> <hash> <stage>\t<path>`; a conflicted gitlink has one entry
+    // per stage, and the first one is kept.
+    for entry in out.split('\0') {
+        let Some((meta, file)) = entry.split_once('\t') else {
+            continue;
+        };
+        let mut fields = meta.split(' ');
+        let (Some(GITLINK_MODE), Some(hash)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if subs.last().is_some_and(|s| s.path == file) {
+            continue;
+        }
+        subs.push(Submodule {
+            path: file.to_string(),
+            recorded_commit: hash.to_string(),
+        });
+    }
+    subs
+}
+
+/// Initialized submodules have a `.git` file (or directory, for old ones).
+pub(super) fn is_initialized(path: &str, sub: &Submodule) -> bool {
+    Path::new(path).join(&sub.path).join(".git").exists()
+}
+
+/// `clone_path` of a submodule, in the same `/workspace/...` form as its
+/// parent's.
+pub(super) fn nested_clone_path(clone_path: &str, sub: &Submodule) -> String {
+    format!("{}/{}", clone_path.trim_end_matches('/'), sub.path)
+}
+
+fn not_initialized(clone_path: String) -> GitRepoStatus {
+    GitRepoStatus {
+        path: clone_path,
+        branch: None,
+        dirty: false,
+        ahead: 0,
+        behind: 0,
+        last_commit: None,
+        files: None,
+        stash_count: None,
+        submodules: Vec::new(),
+        error: Some("Submodule not initialized".to_string()),
+    }
+}
+
+/// Status of each submodule of the repo at `path`, recursing through
+/// `repo_status`.
+pub(super) async fn submodule_statuses(
+    path: &str,
+    clone_path: &str,
+    detailed: bool,
+) -> Vec<GitSubmoduleStatus> {
+    let mut statuses = Vec::new();
+    for sub in submodules(path).await {
+        let nested = nested_clone_path(clone_path, &sub);
+        if !is_initialized(path, &sub) {
+            statuses.push(GitSubmoduleStatus {
+                status: not_initialized(nested),
+                initialized: false,
+                recorded_commit: sub.recorded_commit,
+                head_commit: None,
+                out_of_sync: false,
+            });
+            continue;
+        }
+        let status = repo_status(nested.clone(), detailed).await;
+        let (code, head, _) = run_as_dev(&format!(
+            "git -C '{}' rev-parse --verify --quiet HEAD",
+            full_path(&nested)
+        ))
+        .await;
+        let head_commit = (code == 0).then(|| head.trim().to_string());
+        statuses.push(GitSubmoduleStatus {
+            status,
+            initialized: true,
+            out_of_sync: head_commit.as_deref() != Some(sub.recorded_commit.as_str()),
+            recorded_commit: sub.recorded_commit,
+            head_commit,
+        });
+    }
+    statuses
+}
@@ -0,0 +1,359 @@
+// Linked worktrees let several tasks work on one repository side by side.
+// Worktree paths are reported in the same `/home/dev`-relative form as
+// `repoPath`, so any git route can be pointed at a worktree directly.
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+use super::{full_path, handle_json_request, run_as_dev, shell_quote};
+use crate::path_policy::{PathAccess, resolve};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorktreeListBody {
+    repo_path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorktreeAddBody {
+    repo_path: String,
+    /// New branch checked out in the worktree.
+    branch: String,
+    /// Start point for the branch; HEAD of the main worktree by default.
+    #[serde(default)]
+    base: Option<String>,
+    /// Where to create it (`repoPath` form). Defaults to
+    /// `<repoPath>-worktrees/<branch>`.
+    #[serde(default)]
+    worktree_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorktreeRemoveBody {
+    repo_path: String,
+    worktree_path: String,
+    /// Remove even with uncommitted or untracked changes.
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitWorktree {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    head: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    main: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    detached: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    locked: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    prunable: bool,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitWorktreeListResponse {
+    path: String,
+    worktrees: Vec<GitWorktree>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct GitWorktreeActionResponse {
+    path: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    worktree: Option<GitWorktree>,
+    /// Files with changes that blocked a non-forced remove.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dirty: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pruned: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GitWorktreeActionResponse {
+    fn failed(path: String, error: impl Into<String>) -> Self {
+        Self {
+            path,
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Inverse of `full_path`; paths outside `/home/dev` stay absolute.
+fn repo_relative(abs: &str) -> String {
+    abs.strip_prefix("/home/dev")
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(abs)
+        .to_string()
+}
+
+fn parse_worktrees(out: &str) -> Vec<GitWorktree> {
+    // `--porcelain -z`: attributes are NUL-terminated, entries end with an
+    // empty attribute. The first entry is always the main worktree.
+    let mut worktrees = Vec::new();
+    let mut current: Option<GitWorktree> = None;
+    for attr in out.split('\0') {
+        if attr.is_empty() {
+            worktrees.extend(current.take());
+            continue;
+        }
+        let (key, value) = attr.split_once(' ').unwrap_or((attr, ""));
+        if key == "worktree" {
+            worktrees.extend(current.take());
+            current = Some(GitWorktree {
+                path: repo_relative(value),
+                main: worktrees.is_empty(),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(wt) = current.as_mut() else {
+            continue;
+        };
+        match key {
+            "HEAD" => wt.head = Some(value.to_string()),
+            "branch" => {
+                wt.branch = Some(
+                    value
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(value)
+                        .to_string(),
+                )
+            }
+            "detached" => wt.detached = true,
+            "locked" => wt.locked = true,
+            "prunable" => wt.prunable = true,
+            _ => {}
+        }
+    }
+    worktrees.extend(current);
+    worktrees
+}
+
+async fn worktree_entries(path: &str) -> Result<Vec<GitWorktree>, String> {
+    let (code, out, stderr) =
+        run_as_dev(&format!("git -C '{path}' worktree list --porcelain -z")).await;
+    if code != 0 {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(parse_worktrees(&out))
+}
+
+async fn list(body: WorktreeListBody) -> GitWorktreeListResponse {
+    let path = full_path(&body.repo_path);
+    match worktree_entries(&path).await {
+        Ok(worktrees) => GitWorktreeListResponse {
+            path: body.repo_path,
+            worktrees,
+            error: None,
+        },
+        Err(e) => GitWorktreeListResponse {
+            path: body.repo_path,
+            error: Some(e),
+            ..Default::default()
+        },
+    }
+}
+
+async fn add(body: WorktreeAddBody) -> GitWorktreeActionResponse {
+    let path = full_path(&body.repo_path);
+
+    let (code, _, _) = run_as_dev(&format!(
+        "git -C '{path}' check-ref-format --branch {}",
+        shell_quote(&body.branch)
+    ))
+    .await;
+    if code != 0 || body.branch.starts_with('-') {
+        return GitWorktreeActionResponse::failed(
+            body.repo_path,
+            format!("Invalid branch name: {}", body.branch),
+        );
+    }
+    if let Some(base) = body.base.as_deref()
+        && base.starts_with('-')
+    {
+        return GitWorktreeActionResponse::failed(body.repo_path, format!("Invalid base: {base}"));
+    }
+
+    let worktree_path = body.worktree_path.clone().unwrap_or_else(|| {
+        format!(
+            "{}-worktrees/{}",
+            body.repo_path.trim_end_matches('/'),
+            body.branch.replace('/', "-")
+        )
+    });
+    let target = match resolve(&full_path(&worktree_path), PathAccess::Write) {
+        Ok(p) => p.to_string_lossy().into_owned(),
+        Err(e) => return GitWorktreeActionResponse::failed(body.repo_path, e),
+    };
+    if std::fs::read_dir(&target).is_ok_and(|mut d| d.next().is_some()) {
+        return GitWorktreeActionResponse::failed(
+            body.repo_path,
+            format!("Destination is not empty: {worktree_path}"),
+        );
+    }
+
+    let base = body
+        .base
+        .as_deref()
+        .map(|b| format!(" {}", shell_quote(b)))
+        .unwrap_or_default();
+    let (code, _, stderr) = run_as_dev(&format!(
+        "git -C '{path}' worktree add -b {} {}{base}",
+        shell_quote(&body.branch),
+        shell_quote(&target)
+    ))
+    .await;
+    if code != 0 {
+        return GitWorktreeActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    let worktree = worktree_entries(&path)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|wt| wt.branch.as_deref() == Some(body.branch.as_str()));
+    GitWorktreeActionResponse {
+        path: body.repo_path,
+        success: true,
+        worktree,
+        ..Default::default()
+    }
+}
+
+async fn remove(body: WorktreeRemoveBody) -> GitWorktreeActionResponse {
+    let path = full_path(&body.repo_path);
+    let worktree = worktree_entries(&path)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|wt| wt.path.trim_end_matches('/') == body.worktree_path.trim_end_matches('/'));
+    let Some(worktree) = worktree else {
+        return GitWorktreeActionResponse::failed(
+            body.repo_path,
+            format!("Not a worktree of this repository: {}", body.worktree_path),
+        );
+    };
+    if worktree.main {
+        return GitWorktreeActionResponse::failed(
+            body.repo_path,
+            "Cannot remove the main worktree",
+        );
+    }
+
+    let wt_path = full_path(&worktree.path);
+    if !body.force && !worktree.prunable {
+        let (code, out, stderr) = run_as_dev(&format!(
+            "git -C {} status --porcelain -z --untracked-files=all",
+            shell_quote(&wt_path)
+        ))
+        .await;
+        if code != 0 {
+            return GitWorktreeActionResponse::failed(body.repo_path, stderr.trim());
+        }
+        // Entries are "XY path"; renames add the original path as an extra
+        // field without a status prefix, which `get(3..)` drops.
+        let dirty: Vec<String> = out
+            .split('\0')
+            .filter(|e| e.len() > 3 && e.as_bytes()[2] == b' ')
+            .filter_map(|e| e.get(3..).map(str::to_string))
+            .collect();
+        if !dirty.is_empty() {
+            return GitWorktreeActionResponse {
+                dirty,
+                worktree: Some(worktree),
+                ..GitWorktreeActionResponse::failed(
+                    body.repo_path,
+                    "Worktree has uncommitted changes",
+                )
+            };
+        }
+    }
+
+    // Locked worktrees need the force flag twice.
+    let force = match (body.force, worktree.locked) {
+        (true, true) => " --force --force",
+        (true, false) => " --force",
+        _ => "",
+    };
+    let (code, _, stderr) = run_as_dev(&format!(
+        "git -C '{path}' worktree remove{force} {}",
+        shell_quote(&wt_path)
+    ))
+    .await;
+    if code != 0 {
+        return GitWorktreeActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    GitWorktreeActionResponse {
+        path: body.repo_path,
+        success: true,
+        worktree: Some(worktree),
+        ..Default::default()
+    }
+}
+
+async fn prune(body: WorktreeListBody) -> GitWorktreeActionResponse {
+    let path = full_path(&body.repo_path);
+    let (code, out, stderr) =
+        run_as_dev(&format!("git -C '{path}' worktree prune --verbose")).await;
+    if code != 0 {
+        return GitWorktreeActionResponse::failed(body.repo_path, stderr.trim());
+    }
+
+    // "Removing worktrees/<name>: gitdir file points to non-existent location"
+    let pruned = format!("{out}{stderr}")
+        .lines()
+        .filter_map(|l| l.strip_prefix("Removing worktrees/"))
+        .map(|l| l.split_once(':').map_or(l, |(name, _)| name).to_string())
+        .collect();
+    GitWorktreeActionResponse {
+        path: body.repo_path,
+        success: true,
+        pruned,
+        ..Default::default()
+    }
+}
+
+pub async fn handle_git_worktrees(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(
+        req,
+        |_, e| GitWorktreeListResponse {
+            error: Some(e),
+            ..Default::default()
+        },
+        list,
+    )
+    .await
+}
+
+pub async fn handle_git_worktree_add(req: Request<hyper::body::Incoming>) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitWorktreeActionResponse::failed, add).await
+}
+
+pub async fn handle_git_worktree_remove(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitWorktreeActionResponse::failed, remove).await
+}
+
+pub async fn handle_git_worktree_prune(
+    req: Request<hyper::body::Incoming>,
+) -> Response<Full<Bytes>> {
+    handle_json_request(req, GitWorktreeActionResponse::failed, prune).await
+}
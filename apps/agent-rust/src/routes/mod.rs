@@ -1,4 +1,5 @@
 pub mod exec;
+pub mod file_sync;
 pub mod files;
 pub mod git;
 pub mod health;
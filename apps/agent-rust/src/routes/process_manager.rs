@@ -122,24 +122,19 @@ pub async fn read_logs(
         }
     };
 
-    if offset > 0 {
-        if file.seek(std::io::SeekFrom::Start(offset)).await.is_err() {
-            return json_ok(
-                serde_json::to_value(LogsResponse {
-                    name: name.to_string(),
-                    content: String::new(),
-                    next_offset: offset,
-                })
-                .unwrap(),
-            );
-        }
+    if offset > 0 && file.seek(std::io::SeekFrom::Start(offset)).await.is_err() {
+        return json_ok(
+            serde_json::to_value(LogsResponse {
+                name: name.to_string(),
+                content: String::new(),
+                next_offset: offset,
+            })
+            .unwrap(),
+        );
     }
 
     let mut buf = vec![0u8; limit];
-    let n = match file.read(&mut buf).await {
-        Ok(n) => n,
-        Err(_) => 0,
-    };
+    let n = file.read(&mut buf).await.unwrap_or_default();
     buf.truncate(n);
 
     let content = String::from_utf8_lossy(&buf).to_string();
@@ -238,23 +233,23 @@ impl ProcessRegistry {
         tokio::spawn(async move {
             let status = child.wait().await;
             let mut procs = processes_arc.lock().await;
-            if let Some(proc_entry) = procs.get_mut(&name_owned) {
-                if proc_entry.pid == child_pid {
-                    match status {
-                        Ok(s) => {
-                            let code = s.code().unwrap_or(-1);
-                            proc_entry.exit_code = Some(code);
-                            proc_entry.status = if code == 0 {
-                                ProcessStatus::Stopped
-                            } else {
-                                ProcessStatus::Error
-                            };
-                            proc_entry.running = false;
-                        }
-                        Err(_) => {
-                            proc_entry.status = ProcessStatus::Error;
-                            proc_entry.running = false;
-                        }
+            if let Some(proc_entry) = procs.get_mut(&name_owned)
+                && proc_entry.pid == child_pid
+            {
+                match status {
+                    Ok(s) => {
+                        let code = s.code().unwrap_or(-1);
+                        proc_entry.exit_code = Some(code);
+                        proc_entry.status = if code == 0 {
+                            ProcessStatus::Stopped
+                        } else {
+                            ProcessStatus::Error
+                        };
+                        proc_entry.running = false;
+                    }
+                    Err(_) => {
+                        proc_entry.status = ProcessStatus::Error;
+                        proc_entry.running = false;
                     }
                 }
             }
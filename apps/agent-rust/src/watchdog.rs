@@ -23,7 +23,7 @@ fn read_rss_bytes() -> Option<u64> {
         let Some(rest) = line.strip_prefix("VmRSS:") else {
             continue;
         };
-        let kb: u64 = rest.trim().split_whitespace().next()?.parse().ok()?;
+        let kb: u64 = rest.split_whitespace().next()?.parse().ok()?;
         return Some(kb.saturating_mul(1024));
     }
     None
@@ -60,23 +60,23 @@ pub fn start() {
     });
 
     // Optional memory watchdog: abort if RSS exceeds SANDBOX_AGENT_MAX_RSS_MB.
-    if let Ok(limit) = std::env::var("SANDBOX_AGENT_MAX_RSS_MB") {
-        if let Ok(limit_mb) = limit.trim().parse::<u64>() {
-            let limit_bytes = limit_mb.saturating_mul(1024).saturating_mul(1024);
-            std::thread::spawn(move || loop {
-                std::thread::sleep(MEM_CHECK_INTERVAL);
-                let Some(rss) = read_rss_bytes() else {
-                    continue;
-                };
-                if rss > limit_bytes {
-                    eprintln!(
-                        "watchdog: RSS {}MB exceeds limit {}MB, aborting",
-                        rss / 1024 / 1024,
-                        limit_mb
-                    );
-                    std::process::abort();
-                }
-            });
-        }
+    if let Ok(limit) = std::env::var("SANDBOX_AGENT_MAX_RSS_MB")
+        && let Ok(limit_mb) = limit.trim().parse::<u64>()
+    {
+        let limit_bytes = limit_mb.saturating_mul(1024).saturating_mul(1024);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(MEM_CHECK_INTERVAL);
+            let Some(rss) = read_rss_bytes() else {
+                continue;
+            };
+            if rss > limit_bytes {
+                eprintln!(
+                    "watchdog: RSS {}MB exceeds limit {}MB, aborting",
+                    rss / 1024 / 1024,
+                    limit_mb
+                );
+                std::process::abort();
+            }
+        });
     }
 }
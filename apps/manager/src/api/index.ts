@@ -9,6 +9,7 @@ export { eventsRoutes } from "./events.routes.ts";
 export { githubApiRoutes } from "./github-api.routes.ts";
 export { healthRoutes } from "./health.routes.ts";
 export { imageRoutes } from "./image.routes.ts";
+export { internalGitCredentialsRoutes } from "./internal-git-credentials.routes.ts";
 export { internalWellKnownRoutes } from "./internal-well-known.routes.ts";
 export { organizationRoutes } from "./organization.routes.ts";
 export { publicConfigRoutes } from "./public-config.routes.ts";
@@ -0,0 +1,78 @@
+import { Elysia } from "elysia";
+import { sandboxService, userService } from "../container.ts";
+import {
+  GitCredentialRequestSchema,
+  GitCredentialResponseSchema,
+} from "../schemas/git-credentials.ts";
+import {
+  ForbiddenError,
+  NotFoundError,
+  ValidationError,
+} from "../shared/errors.ts";
+import { createInternalGuard, getRequestIp } from "../shared/lib/internal.ts";
+
+// How long the agent may cache a credential. Kept short so a token revoked
+// or rotated on the user's side stops working in sandboxes soon after.
+const GIT_CREDENTIAL_TTL_SECONDS = 300;
+
+function isSandboxId(value: string): boolean {
+  return /^[a-z0-9]{12}$/.test(value);
+}
+
+const internalGuard = createInternalGuard(() => sandboxService.getAll());
+
+export const internalGitCredentialsRoutes = new Elysia({
+  prefix: "/internal",
+}).guard({ beforeHandle: internalGuard }, (app) =>
+  app.post(
+    "/git/credentials",
+    ({ body, request, server }) => {
+      const headerSandboxId =
+        request.headers.get("x-atelier-sandbox-id") ?? undefined;
+      const sandboxId = [body.sandboxId, headerSandboxId].find(
+        (id) => id !== undefined && isSandboxId(id),
+      );
+      if (!sandboxId) {
+        throw new ValidationError("Missing or invalid sandboxId");
+      }
+
+      const sandbox = sandboxService.getById(sandboxId);
+      if (!sandbox) {
+        throw new NotFoundError("Sandbox", sandboxId);
+      }
+
+      // Unlike the well-known config this hands out a token, so a caller
+      // whose address can't be told is refused too.
+      const callerIp = getRequestIp(request, server);
+      if (!callerIp || callerIp !== sandbox.runtime.ipAddress) {
+        throw new ForbiddenError("Caller is not this sandbox");
+      }
+
+      // Only GitHub tokens are held; git falls through to its other helpers
+      // for anything else.
+      if (body.protocol !== "https" || body.host !== "github.com") {
+        throw new NotFoundError("Git credential", body.host);
+      }
+
+      const token = userService.resolveGitHubToken(sandbox.createdBy);
+      if (!token) {
+        throw new NotFoundError("Git credential", body.host);
+      }
+
+      return {
+        username: "x-access-token",
+        password: token,
+        expiresIn: GIT_CREDENTIAL_TTL_SECONDS,
+      };
+    },
+    {
+      body: GitCredentialRequestSchema,
+      response: GitCredentialResponseSchema,
+      detail: {
+        tags: ["github"],
+        description:
+          "Internal git credential endpoint for the sandbox agent's credential helper (sandbox-only access).",
+      },
+    },
+  ),
+);
@@ -11,6 +11,7 @@ import {
   githubApiRoutes,
   healthRoutes,
   imageRoutes,
+  internalGitCredentialsRoutes,
   internalWellKnownRoutes,
   organizationRoutes,
   publicConfigRoutes,
@@ -208,6 +209,7 @@ const app = new Elysia()
   .use(healthRoutes)
   .use(publicConfigRoutes)
   .use(internalWellKnownRoutes)
+  .use(internalGitCredentialsRoutes)
   .use(authRoutes)
   .use(mcpRoutes)
   .group("/api", (app) =>
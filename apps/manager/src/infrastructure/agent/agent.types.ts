@@ -51,6 +51,36 @@ export interface BatchExecResult {
   results: (ExecResult & { id: string })[];
 }
 
+export type GitFileState =
+  | "unmodified"
+  | "modified"
+  | "typechange"
+  | "added"
+  | "deleted"
+  | "renamed"
+  | "copied"
+  | "unmerged";
+
+export interface GitFileStatus {
+  path: string;
+  origPath?: string;
+  index: GitFileState;
+  worktree: GitFileState;
+  untracked?: boolean;
+  conflict?:
+    | "both_modified"
+    | "both_added"
+    | "both_deleted"
+    | "added_by_us"
+    | "added_by_them"
+    | "deleted_by_us"
+    | "deleted_by_them";
+  lines?: number;
+  binary?: boolean;
+  submodule?: boolean;
+  lfs?: boolean;
+}
+
 export interface GitRepoStatus {
   path: string;
   branch: string | null;
@@ -58,17 +88,48 @@ export interface GitRepoStatus {
   ahead: number;
   behind: number;
   lastCommit: string | null;
+  files?: GitFileStatus[];
+  stashCount?: number;
+  submodules?: GitSubmoduleStatus[];
   error?: string;
 }
 
+export interface GitSubmoduleStatus extends GitRepoStatus {
+  initialized: boolean;
+  recordedCommit: string;
+  headCommit?: string;
+  outOfSync: boolean;
+}
+
 export interface GitStatus {
   repos: GitRepoStatus[];
 }
 
+export interface GitStatusEvent {
+  seq: number;
+  at: string;
+  status: GitRepoStatus;
+}
+
+export interface GitEventsResult {
+  events: GitStatusEvent[];
+  nextSeq: number;
+  snapshot?: boolean;
+}
+
+export interface GitLfsChange {
+  oldOid?: string;
+  oldSize?: number;
+  newOid?: string;
+  newSize?: number;
+}
+
 export interface GitDiffFile {
   path: string;
   added: number;
   removed: number;
+  submodule?: boolean;
+  lfs?: GitLfsChange;
 }
 
 export interface GitDiffRepo {
@@ -76,6 +137,7 @@ export interface GitDiffRepo {
   files: GitDiffFile[];
   totalAdded: number;
   totalRemoved: number;
+  submodules?: GitDiffRepo[];
   error?: string;
 }
 
@@ -83,16 +145,50 @@ export interface GitDiffResult {
   repos: GitDiffRepo[];
 }
 
+export interface GitCheckResult {
+  name: string;
+  source: "config" | "pre-commit" | "husky" | "lint-staged";
+  command: string;
+  passed: boolean;
+  exitCode: number;
+  durationMs: number;
+  timedOut?: boolean;
+  output: string;
+}
+
 export interface GitCommitResult {
   path: string;
   success: boolean;
   hash?: string;
+  fullHash?: string;
+  files?: string[];
+  hookFailure?: {
+    hook: "pre-commit" | "prepare-commit-msg" | "commit-msg";
+    output: string;
+  };
+  checks?: GitCheckResult[];
   error?: string;
 }
 
+export type GitPushErrorCode =
+  | "non_fast_forward"
+  | "auth_failed"
+  | "protected_branch"
+  | "hook_rejected"
+  | "network"
+  | "unknown";
+
 export interface GitPushResult {
   path: string;
   success: boolean;
+  remote?: string;
+  remoteUrl?: string;
+  branch?: string;
+  ref?: string;
+  newBranch?: boolean;
+  upToDate?: boolean;
+  setUpstream?: boolean;
+  code?: GitPushErrorCode;
   error?: string;
 }
 
@@ -102,16 +198,27 @@ export interface DevLogsResult {
   nextOffset: number;
 }
 
+export type FileWriteMode =
+  | "overwrite"
+  | "append"
+  | "createOnly"
+  | "ensureLine"
+  | "managedBlock";
+
 export interface FileWrite {
   path: string;
   content: string;
   mode?: string;
   owner?: "dev" | "root";
+  writeMode?: FileWriteMode;
+  beginMarker?: string;
+  endMarker?: string;
 }
 
 export interface FileWriteResult {
   path: string;
   success: boolean;
+  changed: boolean;
   error?: string;
 }
 
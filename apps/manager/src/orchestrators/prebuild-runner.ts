@@ -514,12 +514,12 @@ export class PrebuildRunner {
     }
 
     // Write secrets and file secrets (needed by init commands)
-    const [secretFiles, gitCredFiles, fileSecretFiles] = await Promise.all([
+    const [secretFiles, gitConfigFiles, fileSecretFiles] = await Promise.all([
       GuestOps.collectSecretFiles(workspace),
-      GuestOps.collectGitCredentialFiles(githubToken),
+      GuestOps.collectGitConfigFiles(),
       GuestOps.collectFileSecretFiles(workspace),
     ]);
-    const allFiles = [...secretFiles, ...gitCredFiles, ...fileSecretFiles];
+    const allFiles = [...secretFiles, ...gitConfigFiles, ...fileSecretFiles];
     if (allFiles.length > 0) {
       log.info(
         { sandboxId, fileCount: allFiles.length },
@@ -115,13 +115,7 @@ export class SandboxLifecycle {
       : undefined;
 
     if (workspace) {
-      return restartWorkspaceSandbox(
-        sandboxId,
-        sandbox,
-        workspace,
-        this.ports,
-        sandbox.createdBy,
-      );
+      return restartWorkspaceSandbox(sandboxId, sandbox, workspace, this.ports);
     }
 
     return restartWorkspacelessSandbox(sandboxId, sandbox, this.ports);
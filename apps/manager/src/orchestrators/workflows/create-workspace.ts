@@ -81,12 +81,12 @@ export async function createWorkspaceSandbox(
       : undefined;
     const githubToken = ports.users.resolveGitHubToken(createdByUserId);
 
-    const [secretFiles, gitCredFiles, fileSecretFiles] = await timer.step(
+    const [secretFiles, gitConfigFiles, fileSecretFiles] = await timer.step(
       "collect_secrets",
       () =>
         Promise.all([
           GuestOps.collectSecretFiles(workspace),
-          GuestOps.collectGitCredentialFiles(githubToken, gitUserIdentity),
+          GuestOps.collectGitConfigFiles(gitUserIdentity),
           GuestOps.collectFileSecretFiles(workspace),
         ]),
     );
@@ -98,7 +98,7 @@ export async function createWorkspaceSandbox(
           ...GuestOps.buildRuntimeEnvFiles({ ATELIER_SANDBOX_ID: sandboxId }),
           ...GuestOps.buildSandboxMdFile(mdContent),
           ...secretFiles,
-          ...gitCredFiles,
+          ...gitConfigFiles,
           ...fileSecretFiles,
         ]),
       ]),
@@ -16,7 +16,6 @@ export async function restartWorkspaceSandbox(
   sandbox: Sandbox,
   workspace: Workspace,
   ports: SandboxPorts,
-  createdByUserId?: string,
 ): Promise<Sandbox> {
   // Re-register the derived CLIProxy key (idempotent) so a CLIProxy that lost
   // its key store recovers on restart; overlaps boot, awaited before finalize.
@@ -27,13 +26,12 @@ export async function restartWorkspaceSandbox(
     );
 
   const boot = await bootExistingSandbox(sandboxId, sandbox, ports);
-  const githubToken = ports.users.resolveGitHubToken(createdByUserId);
 
   if (boot.agentReady) {
     // --- Collect files (parallel async prep) ---
-    const [secretFiles, gitCredFiles, fileSecretFiles] = await Promise.all([
+    const [secretFiles, gitConfigFiles, fileSecretFiles] = await Promise.all([
       GuestOps.collectSecretFiles(workspace),
-      GuestOps.collectGitCredentialFiles(githubToken),
+      GuestOps.collectGitConfigFiles(),
       GuestOps.collectFileSecretFiles(workspace),
     ]);
 
@@ -43,9 +41,10 @@ export async function restartWorkspaceSandbox(
       ports.agent.writeFiles(sandboxId, [
         ...GuestOps.buildRuntimeEnvFiles({ ATELIER_SANDBOX_ID: sandboxId }),
         ...secretFiles,
-        ...gitCredFiles,
+        ...gitConfigFiles,
         ...fileSecretFiles,
       ]),
+      GuestOps.removeLegacyGitCredentials(ports.agent, sandboxId),
     ]);
     log.info(
       {
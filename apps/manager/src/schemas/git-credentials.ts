@@ -0,0 +1,17 @@
+import type { Static } from "elysia";
+import { t } from "elysia";
+
+export const GitCredentialRequestSchema = t.Object({
+  sandboxId: t.Optional(t.String()),
+  protocol: t.String(),
+  host: t.String(),
+  path: t.Optional(t.Union([t.String(), t.Null()])),
+});
+export type GitCredentialRequest = Static<typeof GitCredentialRequestSchema>;
+
+export const GitCredentialResponseSchema = t.Object({
+  username: t.String(),
+  password: t.String(),
+  expiresIn: t.Number(),
+});
+export type GitCredentialResponse = Static<typeof GitCredentialResponseSchema>;
@@ -2,6 +2,7 @@ export * from "./api-key.ts";
 export * from "./common.ts";
 export * from "./config.ts";
 export * from "./events.ts";
+export * from "./git-credentials.ts";
 export * from "./github.ts";
 export * from "./image.ts";
 export * from "./organization.ts";
@@ -22,6 +22,16 @@ export type SandboxServiceEntry = Static<typeof SandboxServiceEntrySchema>;
 const RepoConfigSchema = Type.Object({
   clonePath: Type.String(),
   branch: Type.String(),
+  // Commands `POST /git/checks` runs (in the repo, as dev) on top of the
+  // pre-commit/husky/lint-staged setup it detects in the repo itself.
+  checks: Type.Optional(
+    Type.Array(
+      Type.Object({
+        name: Type.String(),
+        command: Type.String(),
+      }),
+    ),
+  ),
 });
 
 export const SandboxConfigSchema = Type.Object({
@@ -46,6 +56,18 @@ export const SandboxConfigSchema = Type.Object({
       appPort: Type.Number(),
     }),
   ),
+  // Filesystem policy enforced by the agent on every file endpoint and on
+  // exec `workdir`. Writes must land under one of `writableRoots` (after
+  // symlink resolution); `protectedPaths` are refused for any access, on top
+  // of the agent's built-in list (this config file, the agent binary).
+  // Optional: when absent the agent allows writes anywhere but still
+  // enforces its built-in protected paths.
+  pathPolicy: Type.Optional(
+    Type.Object({
+      writableRoots: Type.Optional(Type.Array(Type.String())),
+      protectedPaths: Type.Optional(Type.Array(Type.String())),
+    }),
+  ),
 });
 
 export type SandboxConfig = Static<typeof SandboxConfigSchema>;